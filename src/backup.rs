@@ -0,0 +1,280 @@
+use crate::error::AppError;
+use crate::settings::write_copy;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{debug, info};
+
+/// Number of backups that are kept for a configuration file unless `[sssh] backup_count` is set.
+pub const DEFAULT_BACKUP_COUNT: usize = 10;
+
+/// A copy of configuration file from before sssh changed it.
+///
+/// Backups are kept in `.<FILENAME>.backups` next to configuration file and each one is named
+/// after when it's taken, e.g. `20230305-142501-123.toml` (UTC).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Backup {
+    pub filename: PathBuf,
+    /// Milliseconds since UNIX epoch.
+    pub timestamp: u64,
+}
+
+impl Backup {
+    pub fn try_read(&self) -> Result<String, AppError> {
+        fs::read_to_string(self.filename.clone()).map_err(|error| AppError::FileRead {
+            title: "backup",
+            filename: self.filename.clone(),
+            source: error,
+        })
+    }
+
+    /// Date and time in UTC, e.g. `2023-03-05 14:25:01`.
+    pub fn time(&self) -> String {
+        let (date, time) = split_timestamp(self.timestamp);
+        format!(
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            date.0, date.1, date.2, time.0, time.1, time.2
+        )
+    }
+}
+
+/// Directory of backups of `configuration_file`, e.g. `.sssh.toml.backups`.
+pub fn directory(configuration_file: &Path) -> PathBuf {
+    let filename = configuration_file
+        .file_name()
+        .map(|filename| filename.to_string_lossy().to_string())
+        .unwrap_or_default();
+    configuration_file.with_file_name(format!(".{}.backups", filename.trim_start_matches('.')))
+}
+
+/// Backups of configuration file, newest first.
+pub fn try_list(configuration_file: &Path) -> Result<Vec<Backup>, AppError> {
+    let directory = directory(configuration_file);
+    if !directory.exists() {
+        return Ok(Vec::new());
+    }
+    let mut backup_list: Vec<_> = fs::read_dir(directory.clone())
+        .map_err(|error| AppError::FileRead {
+            title: "backup directory",
+            filename: directory.clone(),
+            source: error,
+        })?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter_map(|filename| {
+            let timestamp = parse_timestamp(&filename.file_stem()?.to_string_lossy())?;
+            Some(Backup {
+                filename,
+                timestamp,
+            })
+        })
+        .collect();
+    backup_list.sort_by_key(|backup| std::cmp::Reverse(backup.timestamp));
+    Ok(backup_list)
+}
+
+/// Copies current contents of configuration file to a new backup (unless the newest backup is
+/// the same) and removes backups beyond `backup_count`. A `backup_count` of 0 disables backups.
+pub fn try_save(configuration_file: &Path, backup_count: usize) -> Result<(), AppError> {
+    if backup_count == 0 || !configuration_file.exists() {
+        return Ok(());
+    }
+    let configuration =
+        fs::read_to_string(configuration_file).map_err(|error| AppError::FileRead {
+            title: "configuration",
+            filename: configuration_file.to_path_buf(),
+            source: error,
+        })?;
+    let mut backup_list = try_list(configuration_file)?;
+    if backup_list
+        .first()
+        .and_then(|backup| backup.try_read().ok())
+        .is_some_and(|backup| backup == configuration)
+    {
+        debug!("Newest backup is the same as configuration file");
+    } else {
+        let directory = directory(configuration_file);
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or_default();
+        // Keep backups taken in the same millisecond apart:
+        let timestamp = backup_list
+            .first()
+            .map_or(timestamp, |backup| timestamp.max(backup.timestamp + 1));
+        let filename = directory.join(format!(
+            "{}.{}",
+            format_timestamp(timestamp),
+            configuration_file
+                .extension()
+                .map(|extension| extension.to_string_lossy().to_string())
+                .unwrap_or_else(|| "toml".to_string())
+        ));
+        fs::create_dir_all(directory.clone())
+            // Backups of a private configuration file are private too:
+            .and_then(|_| write_copy(&filename, &configuration, configuration_file))
+            .map_err(|error| AppError::FileWrite {
+                title: "backup",
+                filename: filename.clone(),
+                source: error,
+            })?;
+        info!(filename = ?filename, "Saved backup of configuration file");
+        backup_list.insert(
+            0,
+            Backup {
+                filename,
+                timestamp,
+            },
+        );
+    }
+    for backup in backup_list.iter().skip(backup_count) {
+        debug!(filename = ?backup.filename, "Remove old backup");
+        fs::remove_file(backup.filename.clone()).map_err(|error| AppError::FileWrite {
+            title: "backup",
+            filename: backup.filename.clone(),
+            source: error,
+        })?;
+    }
+    Ok(())
+}
+
+/// `YYYYMMDD-HHMMSS-mmm` in UTC.
+fn format_timestamp(timestamp: u64) -> String {
+    let ((year, month, day), (hour, minute, second)) = split_timestamp(timestamp);
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}-{:03}",
+        year,
+        month,
+        day,
+        hour,
+        minute,
+        second,
+        timestamp % 1000
+    )
+}
+
+/// Parses `YYYYMMDD-HHMMSS-mmm` (see `format_timestamp`). Other files and impossible dates or
+/// times (e.g. `20230230`) are not backups.
+fn parse_timestamp(text: &str) -> Option<u64> {
+    // Only digits, so slicing by bytes below can't split a character:
+    if !text
+        .bytes()
+        .all(|byte| byte.is_ascii_digit() || byte == b'-')
+    {
+        return None;
+    }
+    let part_list: Vec<_> = text.split('-').collect();
+    let [date, time, millisecond] = part_list[..] else {
+        return None;
+    };
+    if date.len() != 8 || time.len() != 6 || millisecond.len() != 3 {
+        return None;
+    }
+    let number = |text: &str| text.parse::<u64>().ok();
+    let (year, month, day) = (
+        number(&date[..4])?,
+        number(&date[4..6])?,
+        number(&date[6..])?,
+    );
+    let (hour, minute, second) = (
+        number(&time[..2])?,
+        number(&time[2..4])?,
+        number(&time[4..])?,
+    );
+    if !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 59
+    {
+        return None;
+    }
+    // Days since UNIX epoch of a civil date (see `split_timestamp`):
+    let era_year = if month <= 2 {
+        year.checked_sub(1)?
+    } else {
+        year
+    };
+    let era = era_year / 400;
+    let year_of_era = era_year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = (era * 146097 + day_of_era).checked_sub(719468)?;
+    let timestamp =
+        ((days * 86400 + hour * 3600 + minute * 60 + second) * 1000) + number(millisecond)?;
+    // Days that the month doesn't have (e.g. February 30) end up in the next month:
+    (split_timestamp(timestamp).0 == (year, month, day)).then_some(timestamp)
+}
+
+/// Civil date and time of day (UTC) of a timestamp in milliseconds, using Howard Hinnant's
+/// `civil_from_days` algorithm.
+fn split_timestamp(timestamp: u64) -> ((u64, u64, u64), (u64, u64, u64)) {
+    let seconds = timestamp / 1000;
+    let (days, second_of_day) = (seconds / 86400, seconds % 86400);
+    let days = days + 719468;
+    let era = days / 146097;
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (
+        (year, month, day),
+        (
+            second_of_day / 3600,
+            second_of_day % 3600 / 60,
+            second_of_day % 60,
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_and_parse_timestamps() {
+        for timestamp in [0, 951_782_400_123, 1_679_000_000_999, 4_102_444_799_000] {
+            assert_eq!(
+                parse_timestamp(&format_timestamp(timestamp)),
+                Some(timestamp)
+            );
+        }
+        assert_eq!(format_timestamp(0), "19700101-000000-000");
+        // February 29 of a leap year:
+        assert_eq!(format_timestamp(951_782_400_123), "20000229-000000-123");
+    }
+
+    #[test]
+    fn parse_invalid_timestamps() {
+        for text in [
+            "",
+            "notes",
+            "20230317-101010",
+            "20230317-101010-00",
+            "2023031-7101010-000",
+            "20230317-101010-000-1",
+            "2023+317-101010-000",
+            "20230317-1010１0-000",
+            "20231317-101010-000",
+            "20230017-101010-000",
+            "20230300-101010-000",
+            "20230332-101010-000",
+            "20230230-101010-000",
+            "20230229-101010-000",
+            "20230317-241010-000",
+            "20230317-106010-000",
+            "20230317-101060-000",
+            "19691231-235959-999",
+            "00000101-000000-000",
+        ] {
+            assert_eq!(parse_timestamp(text), None, "{}", text);
+        }
+    }
+}
@@ -28,6 +28,47 @@ pub enum AppError {
         filename: PathBuf,
         source: toml::de::Error,
     },
+    #[error("Invalid entry {name:?} in configuration file {filename:?}: {reason:}")]
+    ConfigEntry {
+        filename: PathBuf,
+        name: String,
+        reason: String,
+    },
+    #[error("Server {name:?} is defined in both {filename:?} and {other_filename:?}")]
+    ConfigDuplicateServer {
+        name: String,
+        filename: PathBuf,
+        other_filename: PathBuf,
+    },
+    #[error("Could not include {pattern:?} in configuration file {filename:?}: {reason:}")]
+    ConfigInclude {
+        filename: PathBuf,
+        pattern: String,
+        reason: String,
+    },
+    #[error("Could not load inventory from command {command:?}: {reason:}")]
+    Inventory { command: String, reason: String },
+    #[error("Server {name:?} in configuration file {filename:?} extends {template:?} which does not exist")]
+    ConfigTemplateNotFound {
+        filename: PathBuf,
+        name: String,
+        template: String,
+    },
+    #[error("Servers in configuration file {filename:?} extend each other: {}", .chain.join(" -> "))]
+    ConfigTemplateCycle {
+        filename: PathBuf,
+        chain: Vec<String>,
+    },
+    #[error("Environment variable {variable:?} of {name:?} in configuration file {filename:?} is not set and has no default value")]
+    ConfigVariable {
+        filename: PathBuf,
+        name: String,
+        variable: String,
+    },
+    #[error("Invalid key bindings in configuration file {filename:?}: {reason:}")]
+    ConfigKeyBinding { filename: PathBuf, reason: String },
+    #[error("Invalid theme in {filename:?}: {reason:}")]
+    ConfigTheme { filename: PathBuf, reason: String },
     #[error("{title:} {filename:?} already exists")]
     FileAlreadyExists {
         title: &'static str,
@@ -38,9 +79,19 @@ pub enum AppError {
         title: &'static str,
         command: PathBuf,
     },
+    #[error("Could not edit configuration file {filename:?}")]
+    ConfigEdit {
+        filename: PathBuf,
+        source: toml_edit::TomlError,
+    },
     // Edit subcommand:
     #[error("Editor process was running for less than 2 seconds!\nMaybe your editor opened the edit tab inside another session.")]
     EditorFastStop,
+    #[error("Edited configuration has {error_count} error(s), so configuration file is not changed and your changes are kept in {filename:?}")]
+    ConfigEditInvalid {
+        filename: PathBuf,
+        error_count: usize,
+    },
     #[error(
         "Could not start {title:} process with command {command:?} and arguments {argument_list:?}"
     )]
@@ -65,7 +116,61 @@ pub enum AppError {
         argument_list: Vec<PathBuf>,
         source: io::Error,
     },
+    // Import subcommand:
+    #[error("Could not parse SSH configuration file {filename:?} at line {line:}: {reason:}")]
+    SshConfigSyntax {
+        filename: PathBuf,
+        line: usize,
+        reason: String,
+    },
+    // Connect subcommand:
+    #[error("Could not find server {name:?} in configuration file")]
+    ServerNotFound { name: String },
+    #[error("Username {username:?} is not defined for server {server_name:?} (available usernames: {username_list:?})")]
+    UsernameNotFound {
+        server_name: String,
+        username: String,
+        username_list: Vec<String>,
+    },
+    // Add, remove, rename and set subcommands:
+    #[error("Could not edit server {name:?}: {reason:}")]
+    ServerEdit { name: String, reason: String },
+    // Config subcommand:
+    #[error("Could not find backup {number} of configuration file {filename:?} (it has {count} backup(s), see `config history`)")]
+    BackupNotFound {
+        filename: PathBuf,
+        number: usize,
+        count: usize,
+    },
+    // Check subcommand:
+    #[error("Found {error_count} error(s) in configuration")]
+    ConfigCheck { error_count: usize },
+    // List subcommand:
+    #[error("Could not write to stdout")]
+    StdoutWrite { source: io::Error },
+    // Trust subcommand:
+    #[error("Could not find a project configuration file `.sssh.toml` in {directory:?} or its ancestors (below home directory)")]
+    ProjectConfigNotFound { directory: PathBuf },
     // Select subcommand:
     #[error("UI error")]
     UI { source: io::Error },
 }
+
+impl AppError {
+    /// Errors that can be fixed by editing configuration file.
+    pub fn is_configuration_error(&self) -> bool {
+        matches!(
+            self,
+            Self::ConfigSyntax { .. }
+                | Self::ConfigEntry { .. }
+                | Self::ConfigDuplicateServer { .. }
+                | Self::ConfigInclude { .. }
+                | Self::ConfigTemplateNotFound { .. }
+                | Self::ConfigTemplateCycle { .. }
+                | Self::ConfigVariable { .. }
+                | Self::Inventory { .. }
+                | Self::ConfigKeyBinding { .. }
+                | Self::ConfigTheme { .. }
+        )
+    }
+}
@@ -0,0 +1,308 @@
+/// Upper limit of servers that one pattern can expand to, so a typo like `[1-100000]` fails fast.
+const MAX_EXPANSION_COUNT: usize = 10000;
+
+/// One expanded string and the values that replaced each range or list of the pattern.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expansion {
+    pub value: String,
+    pub part_list: Vec<String>,
+}
+
+/// Why a value could not be interpolated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InterpolationError {
+    /// Name of environment variable that is not set and has no default value.
+    Undefined(String),
+    Invalid(String),
+}
+
+#[derive(Debug, Clone)]
+enum Segment {
+    Literal(String),
+    Choice(Vec<String>),
+}
+
+/// Returns `true` if `pattern` contains a range like `[01-40]` or a list like `{a,b,c}`.
+pub fn is_pattern(pattern: &str) -> bool {
+    parse(pattern)
+        .map(|segment_list| {
+            segment_list
+                .iter()
+                .any(|segment| matches!(segment, Segment::Choice(_)))
+        })
+        .unwrap_or(true)
+}
+
+/// Expands numeric ranges (`[1-3]`, or `[01-40]` which keeps zero-padding) and lists (`{a,b}`)
+/// of `pattern`, e.g. `web[1-2].{eu,us}` is `web1.eu`, `web1.us`, `web2.eu` and `web2.us`.
+///
+/// Brackets and braces that aren't a range or a list (like `[::1]`) are kept as they are.
+pub fn expand(pattern: &str) -> Result<Vec<Expansion>, String> {
+    let mut expansion_list = vec![Expansion {
+        value: String::new(),
+        part_list: Vec::new(),
+    }];
+    for segment in parse(pattern)? {
+        match segment {
+            Segment::Literal(literal) => expansion_list
+                .iter_mut()
+                .for_each(|expansion| expansion.value.push_str(&literal)),
+            Segment::Choice(choice_list) => {
+                if expansion_list.len() * choice_list.len() > MAX_EXPANSION_COUNT {
+                    return Err(format!(
+                        "{:?} expands to more than {} values",
+                        pattern, MAX_EXPANSION_COUNT
+                    ));
+                }
+                expansion_list = expansion_list
+                    .into_iter()
+                    .flat_map(|expansion| {
+                        choice_list.iter().map(move |choice| {
+                            let mut expansion = expansion.clone();
+                            expansion.value.push_str(choice);
+                            expansion.part_list.push(choice.clone());
+                            expansion
+                        })
+                    })
+                    .collect();
+            }
+        }
+    }
+    Ok(expansion_list)
+}
+
+fn parse(pattern: &str) -> Result<Vec<Segment>, String> {
+    let mut segment_list = Vec::new();
+    let mut literal = String::new();
+    let mut rest = pattern;
+    while let Some(character) = rest.chars().next() {
+        let maybe_choice = match character {
+            '[' => rest
+                .find(']')
+                .map(|end| (end, parse_range(&rest[1..end])))
+                .and_then(|(end, maybe_range)| maybe_range.map(|range| (end, range))),
+            '{' => rest
+                .find('}')
+                .map(|end| (end, parse_list(&rest[1..end])))
+                .and_then(|(end, maybe_list)| maybe_list.map(|list| (end, Ok(list)))),
+            _ => None,
+        };
+        match maybe_choice {
+            Some((end, choice_list)) => {
+                if !literal.is_empty() {
+                    segment_list.push(Segment::Literal(std::mem::take(&mut literal)));
+                }
+                segment_list.push(Segment::Choice(choice_list?));
+                rest = &rest[end + 1..];
+            }
+            None => {
+                literal.push(character);
+                rest = &rest[character.len_utf8()..];
+            }
+        }
+    }
+    if !literal.is_empty() {
+        segment_list.push(Segment::Literal(literal));
+    }
+    Ok(segment_list)
+}
+
+/// Parses inside of `[...]`. Returns `None` if it doesn't look like a numeric range at all.
+fn parse_range(range: &str) -> Option<Result<Vec<String>, String>> {
+    let (start, end) = range.split_once('-')?;
+    let is_number = |value: &str| !value.is_empty() && value.chars().all(|c| c.is_ascii_digit());
+    if !is_number(start) || !is_number(end) {
+        return None;
+    }
+    let (Ok(first), Ok(last)) = (start.parse::<u64>(), end.parse::<u64>()) else {
+        return Some(Err(format!("range [{}] is too large", range)));
+    };
+    if first > last {
+        return Some(Err(format!("range [{}] is in descending order", range)));
+    }
+    if last - first >= MAX_EXPANSION_COUNT as u64 {
+        return Some(Err(format!(
+            "range [{}] expands to more than {} values",
+            range, MAX_EXPANSION_COUNT
+        )));
+    }
+    // `[01-40]` is zero-padded to the width of its start, `[1-40]` is not padded:
+    let width = if start.len() > 1 && start.starts_with('0') {
+        start.len()
+    } else {
+        0
+    };
+    Some(Ok((first..=last)
+        .map(|number| format!("{:0width$}", number, width = width))
+        .collect()))
+}
+
+/// Parses inside of `{...}`. Returns `None` if it isn't a comma separated list.
+fn parse_list(list: &str) -> Option<Vec<String>> {
+    if !list.contains(',') {
+        return None;
+    }
+    Some(
+        list.split(',')
+            .map(|item| item.trim().to_string())
+            .collect(),
+    )
+}
+
+/// Replaces `${VAR}` with the value of environment variable `VAR`, and `${VAR:-default}` with
+/// `default` if `VAR` is not set or is empty. `$${` is a literal `${` and any other `$` is kept
+/// as it is.
+pub fn interpolate(value: &str) -> Result<String, InterpolationError> {
+    let mut interpolated = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find('$') {
+        interpolated.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if let Some(after) = rest.strip_prefix("$${") {
+            interpolated.push_str("${");
+            rest = after;
+            continue;
+        }
+        let Some(after) = rest.strip_prefix("${") else {
+            interpolated.push('$');
+            rest = &rest[1..];
+            continue;
+        };
+        let end = after.find('}').ok_or_else(|| {
+            InterpolationError::Invalid(format!("unterminated variable in {:?}", value))
+        })?;
+        let (name, maybe_default) = match after[..end].split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (&after[..end], None),
+        };
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|character| character.is_ascii_alphanumeric() || character == '_')
+        {
+            return Err(InterpolationError::Invalid(format!(
+                "invalid variable name {:?} in {:?}",
+                name, value
+            )));
+        }
+        match (std::env::var(name), maybe_default) {
+            (Ok(variable), Some(default)) if variable.is_empty() => interpolated.push_str(default),
+            (Ok(variable), _) => interpolated.push_str(&variable),
+            (Err(_), Some(default)) => interpolated.push_str(default),
+            (Err(_), None) => return Err(InterpolationError::Undefined(name.to_string())),
+        }
+        rest = &after[end + 1..];
+    }
+    interpolated.push_str(rest);
+    Ok(interpolated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(pattern: &str) -> Vec<String> {
+        expand(pattern)
+            .unwrap()
+            .into_iter()
+            .map(|expansion| expansion.value)
+            .collect()
+    }
+
+    #[test]
+    fn expand_ranges_and_lists() {
+        assert_eq!(values("web[1-3]"), ["web1", "web2", "web3"]);
+        assert_eq!(values("web[08-10]"), ["web08", "web09", "web10"]);
+        assert_eq!(values("web[1-1]"), ["web1"]);
+        assert_eq!(values("{eu, us}.web"), ["eu.web", "us.web"]);
+        assert_eq!(
+            expand("web[1-2].{eu,us}").unwrap()[1],
+            Expansion {
+                value: "web1.us".to_string(),
+                part_list: vec!["1".to_string(), "us".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn expand_keeps_other_brackets() {
+        assert_eq!(values("[::1]"), ["[::1]"]);
+        assert_eq!(values("web[a-b]{x}[1-"), ["web[a-b]{x}[1-"]);
+        assert!(!is_pattern("[::1]"));
+        assert!(is_pattern("web{a,b}"));
+    }
+
+    #[test]
+    fn expand_invalid_ranges() {
+        assert!(expand("web[5-1]").unwrap_err().contains("descending"));
+        assert!(expand("web[1-100000]").unwrap_err().contains("more than"));
+        assert!(expand("[1-99999999999999999999]")
+            .unwrap_err()
+            .contains("too large"));
+        assert!(expand("[1-100][1-100][1-100]")
+            .unwrap_err()
+            .contains("more than"));
+        // Invalid patterns are patterns, so they are reported instead of used as hostnames:
+        assert!(is_pattern("web[5-1]"));
+    }
+
+    #[test]
+    fn expand_empty_parts() {
+        assert_eq!(values(""), [""]);
+        assert_eq!(values("[]"), ["[]"]);
+        assert_eq!(values("[-]"), ["[-]"]);
+        assert_eq!(values("{,a}"), ["", "a"]);
+    }
+
+    #[test]
+    fn interpolate_variables() {
+        std::env::set_var("SSSH_TEST_INTERPOLATE", "value");
+        std::env::set_var("SSSH_TEST_INTERPOLATE_EMPTY", "");
+        std::env::remove_var("SSSH_TEST_INTERPOLATE_UNSET");
+        assert_eq!(
+            interpolate("a-${SSSH_TEST_INTERPOLATE}-b"),
+            Ok("a-value-b".to_string())
+        );
+        assert_eq!(
+            interpolate("${SSSH_TEST_INTERPOLATE_UNSET:-default}"),
+            Ok("default".to_string())
+        );
+        assert_eq!(
+            interpolate("${SSSH_TEST_INTERPOLATE_EMPTY:-default}"),
+            Ok("default".to_string())
+        );
+        assert_eq!(
+            interpolate("${SSSH_TEST_INTERPOLATE:-default}"),
+            Ok("value".to_string())
+        );
+        assert_eq!(
+            interpolate("${SSSH_TEST_INTERPOLATE_UNSET}"),
+            Err(InterpolationError::Undefined(
+                "SSSH_TEST_INTERPOLATE_UNSET".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn interpolate_literals() {
+        assert_eq!(interpolate("$$${x}"), Ok("$${x}".to_string()));
+        assert_eq!(interpolate("$${HOME}"), Ok("${HOME}".to_string()));
+        assert_eq!(interpolate("$HOME $ 5$"), Ok("$HOME $ 5$".to_string()));
+    }
+
+    #[test]
+    fn interpolate_invalid() {
+        assert!(matches!(
+            interpolate("a ${HOME"),
+            Err(InterpolationError::Invalid(_))
+        ));
+        assert!(matches!(
+            interpolate("${}"),
+            Err(InterpolationError::Invalid(_))
+        ));
+        assert!(matches!(
+            interpolate("${A-B}"),
+            Err(InterpolationError::Invalid(_))
+        ));
+    }
+}
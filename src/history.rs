@@ -0,0 +1,142 @@
+use crate::error::AppError;
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{debug, warn};
+
+/// Maximum number of connections that are kept in history file. Older ones are removed.
+pub const MAX_HISTORY_ENTRY_COUNT: usize = 1000;
+
+/// Connections made by `sssh`.
+///
+/// Each line of the history file is `<UNIX TIMESTAMP>\t<SERVER NAME>\t<USERNAME>`.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    pub entry_list: Vec<HistoryEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    pub timestamp: u64,
+    pub server_name: String,
+    pub username: String,
+}
+
+impl History {
+    pub fn try_load(filename: PathBuf) -> Result<Self, AppError> {
+        if !filename.exists() {
+            return Ok(Self::default());
+        }
+        let history = fs::read_to_string(filename.clone()).map_err(|error| AppError::FileRead {
+            title: "history",
+            filename: filename.clone(),
+            source: error,
+        })?;
+        let entry_list = history
+            .lines()
+            .filter_map(|line| {
+                let mut field_list = line.splitn(3, '\t');
+                match (
+                    field_list.next().map(|timestamp| timestamp.parse::<u64>()),
+                    field_list.next(),
+                    field_list.next(),
+                ) {
+                    (Some(Ok(timestamp)), Some(server_name), Some(username)) => {
+                        Some(HistoryEntry {
+                            timestamp,
+                            server_name: server_name.to_string(),
+                            username: username.to_string(),
+                        })
+                    }
+                    _ => {
+                        warn!(line = line, filename = ?filename, "Skip malformed history line");
+                        None
+                    }
+                }
+            })
+            .collect();
+        Ok(Self { entry_list })
+    }
+
+    pub fn try_append(
+        filename: PathBuf,
+        server_name: &str,
+        username: &str,
+    ) -> Result<(), AppError> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+        let write_error = |error| AppError::FileWrite {
+            title: "history",
+            filename: filename.clone(),
+            source: error,
+        };
+        if let Some(directory) = filename.parent() {
+            fs::create_dir_all(directory).map_err(write_error)?;
+        }
+        let line = format!("{}\t{}\t{}\n", timestamp, server_name, username);
+        let history = if filename.exists() {
+            fs::read_to_string(filename.clone()).map_err(|error| AppError::FileRead {
+                title: "history",
+                filename: filename.clone(),
+                source: error,
+            })?
+        } else {
+            String::new()
+        };
+        let line_count = history.lines().count();
+        if line_count < MAX_HISTORY_ENTRY_COUNT {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(filename.clone())
+                .and_then(|mut file| file.write_all(line.as_bytes()))
+                .map_err(write_error)?;
+        } else {
+            // Keep the newest connections only:
+            let mut history: String = history
+                .lines()
+                .skip(line_count + 1 - MAX_HISTORY_ENTRY_COUNT)
+                .map(|line| format!("{}\n", line))
+                .collect();
+            history += &line;
+            fs::write(filename.clone(), history).map_err(write_error)?;
+            debug!(
+                filename = ?filename,
+                removed_count = line_count + 1 - MAX_HISTORY_ENTRY_COUNT,
+                "Removed oldest connections from history file"
+            );
+        }
+        debug!(
+            filename = ?filename,
+            server_name = server_name,
+            username = username,
+            "Appended connection to history file"
+        );
+        Ok(())
+    }
+
+    /// Timestamp of the last connection to each server.
+    pub fn last_used(&self) -> HashMap<String, u64> {
+        let mut last_used = HashMap::new();
+        self.entry_list.iter().for_each(|entry| {
+            let timestamp = last_used.entry(entry.server_name.clone()).or_insert(0);
+            if entry.timestamp > *timestamp {
+                *timestamp = entry.timestamp
+            }
+        });
+        last_used
+    }
+
+    /// Number of connections to each server.
+    pub fn count(&self) -> HashMap<String, usize> {
+        let mut count = HashMap::new();
+        self.entry_list.iter().for_each(|entry| {
+            *count.entry(entry.server_name.clone()).or_insert(0) += 1;
+        });
+        count
+    }
+}
@@ -0,0 +1,191 @@
+use crate::error::AppError;
+use crate::settings::{override_table, ConfigApp, ConfigServer};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{debug, info, warn};
+
+/// External command that prints more servers, from the `[sssh.inventory]` table.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigInventory {
+    /// Command and its arguments. Its stdout must be a JSON array of server objects.
+    #[serde(default)]
+    pub command: Vec<String>,
+    /// Seconds that output of command is cached on disk. `0` disables the cache.
+    #[serde(default)]
+    pub cache_ttl: u64,
+}
+
+/// Last output of inventory command.
+#[derive(Debug, Serialize, Deserialize)]
+struct InventoryCache {
+    timestamp: u64,
+    command: Vec<String>,
+    output: String,
+}
+
+/// Runs inventory command (or uses its cached output) and returns its output.
+///
+/// If the command fails, an expired cache is still better than nothing, so it's used with a
+/// warning.
+pub fn try_load(
+    config_inventory: &ConfigInventory,
+    cache_filename: PathBuf,
+    refresh: bool,
+) -> Result<String, AppError> {
+    let command = config_inventory.command.clone();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    let maybe_cache = if config_inventory.cache_ttl > 0 {
+        read_cache(cache_filename.clone()).filter(|cache| cache.command == command)
+    } else {
+        None
+    };
+    Ok(match maybe_cache {
+        Some(cache) if !refresh && now < cache.timestamp + config_inventory.cache_ttl => {
+            debug!(filename = ?cache_filename, "Use cached inventory");
+            cache.output
+        }
+        maybe_cache => match run(&command) {
+            Ok(output) => {
+                if config_inventory.cache_ttl > 0 {
+                    write_cache(
+                        cache_filename,
+                        InventoryCache {
+                            timestamp: now,
+                            command: command.clone(),
+                            output: output.clone(),
+                        },
+                    )
+                }
+                output
+            }
+            Err(error) => match maybe_cache {
+                Some(cache) => {
+                    warn!(error = ?error, "Could not refresh inventory, use expired cache");
+                    cache.output
+                }
+                None => return Err(error),
+            },
+        },
+    })
+}
+
+fn run(command: &[String]) -> Result<String, AppError> {
+    let inventory_error = |reason: String| AppError::Inventory {
+        command: command.join(" "),
+        reason,
+    };
+    let (program, argument_list) = command
+        .split_first()
+        .ok_or_else(|| inventory_error("command is empty".to_string()))?;
+    debug!(command = ?command, "Attempt to run inventory command");
+    let output = Command::new(program)
+        .args(argument_list)
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|error| inventory_error(error.to_string()))?;
+    if !output.status.success() {
+        return Err(inventory_error(format!(
+            "command exited with {}",
+            output.status
+        )));
+    }
+    info!(command = ?command, "Loaded inventory");
+    String::from_utf8(output.stdout).map_err(|error| inventory_error(error.to_string()))
+}
+
+/// Output is a JSON array of server objects, e.g.
+/// `[{"name": "web1", "hostname": "10.0.0.1", "users": ["admin"]}]`. Fields that a server doesn't
+/// set come from `[[sssh.match]]` rules and `[sssh.defaults]` table of `sssh`.
+pub fn parse(
+    command: &[String],
+    output: &str,
+    sssh: &ConfigApp,
+) -> Result<Vec<ConfigServer>, AppError> {
+    let invalid_output = |reason: String| AppError::Inventory {
+        command: command.join(" "),
+        reason: format!("invalid output: {}", reason),
+    };
+    let object_list: Vec<serde_json::Map<String, serde_json::Value>> =
+        serde_json::from_str(output).map_err(|error| invalid_output(error.to_string()))?;
+    let mut name_set = HashSet::new();
+    object_list
+        .into_iter()
+        .map(|mut object| {
+            let name = match object.remove("name") {
+                Some(serde_json::Value::String(name)) => name,
+                _ => return Err(invalid_output("server without a `name`".to_string())),
+            };
+            let server_error =
+                |error: String| invalid_output(format!("server {:?}: {}", name, error));
+            let object_table =
+                toml::Table::try_from(object).map_err(|error| server_error(error.to_string()))?;
+            // Fields of server itself, to find its name, hostname and tags that rules match:
+            let mut server: ConfigServer = toml::Value::Table(object_table.clone())
+                .try_into()
+                .map_err(|error| server_error(error.to_string()))?;
+            server.name = if server.group.is_empty() {
+                name.clone()
+            } else {
+                format!("{}.{}", server.group, name)
+            };
+            if !name_set.insert(server.name.clone()) {
+                return Err(invalid_output(format!(
+                    "server {:?} is listed more than once",
+                    server.name
+                )));
+            }
+            let mut table = sssh.server_defaults(&server);
+            override_table(&mut table, &object_table);
+            let server = ConfigServer {
+                name: server.name,
+                ..toml::Value::Table(table)
+                    .try_into()
+                    .map_err(|error| server_error(error.to_string()))?
+            };
+            Ok(server)
+        })
+        .collect()
+}
+
+fn read_cache(filename: PathBuf) -> Option<InventoryCache> {
+    if !filename.exists() {
+        return None;
+    }
+    let cache = match fs::read_to_string(filename.clone()) {
+        Ok(cache) => cache,
+        Err(error) => {
+            warn!(error = ?error, filename = ?filename, "Could not read inventory cache");
+            return None;
+        }
+    };
+    match serde_json::from_str(&cache) {
+        Ok(cache) => Some(cache),
+        Err(error) => {
+            warn!(error = ?error, filename = ?filename, "Skip malformed inventory cache");
+            None
+        }
+    }
+}
+
+fn write_cache(filename: PathBuf, cache: InventoryCache) {
+    let cache = serde_json::to_string(&cache).unwrap_or_default();
+    let result = match filename.parent() {
+        Some(directory) => fs::create_dir_all(directory),
+        None => Ok(()),
+    }
+    .and_then(|_| fs::write(filename.clone(), cache));
+    if let Err(error) = result {
+        warn!(error = ?error, filename = ?filename, "Could not write inventory cache");
+    } else {
+        debug!(filename = ?filename, "Updated inventory cache");
+    }
+}
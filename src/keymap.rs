@@ -0,0 +1,424 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::Deserialize;
+use std::fmt;
+
+/// Things a key can do in the terminal UI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyAction {
+    Up,
+    Down,
+    Left,
+    Right,
+    Quit,
+    Edit,
+    AddServer,
+    EditServer,
+    Reload,
+    Choose,
+    Filter,
+    ClearFilter,
+    Sort,
+    RefreshInventory,
+}
+
+impl KeyAction {
+    pub fn all() -> Vec<Self> {
+        [
+            Self::Up,
+            Self::Down,
+            Self::Left,
+            Self::Right,
+            Self::Quit,
+            Self::Edit,
+            Self::AddServer,
+            Self::EditServer,
+            Self::Reload,
+            Self::Choose,
+            Self::Filter,
+            Self::ClearFilter,
+            Self::Sort,
+            Self::RefreshInventory,
+        ]
+        .to_vec()
+    }
+
+    /// Name of the action in the `[sssh.keys]` table.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Up => "up",
+            Self::Down => "down",
+            Self::Left => "left",
+            Self::Right => "right",
+            Self::Quit => "quit",
+            Self::Edit => "edit",
+            Self::AddServer => "add_server",
+            Self::EditServer => "edit_server",
+            Self::Reload => "reload",
+            Self::Choose => "choose",
+            Self::Filter => "filter",
+            Self::ClearFilter => "clear_filter",
+            Self::Sort => "sort",
+            Self::RefreshInventory => "refresh_inventory",
+        }
+    }
+
+    pub fn help(&self) -> &'static str {
+        match self {
+            Self::Up => "Previous server/username",
+            Self::Down => "Next server/username",
+            Self::Left => "Servers/collapse",
+            Self::Right => "Usernames/expand",
+            Self::Quit => "Quit",
+            Self::Edit => "Edit config file",
+            Self::AddServer => "Add server",
+            Self::EditServer => "Edit server",
+            Self::Reload => "Reload config file",
+            Self::Choose => "Choose",
+            Self::Filter => "Filter servers",
+            Self::ClearFilter => "Clear filter",
+            Self::Sort => "Change sort order",
+            Self::RefreshInventory => "Refresh inventory",
+        }
+    }
+
+    fn default_key_list(&self) -> &'static [&'static str] {
+        match self {
+            Self::Up => &["Up"],
+            Self::Down => &["Down"],
+            Self::Left => &["Left", "Backspace"],
+            Self::Right => &["Right"],
+            Self::Quit => &["q"],
+            Self::Edit => &["e"],
+            Self::AddServer => &["a"],
+            Self::EditServer => &["E"],
+            Self::Reload => &["r"],
+            Self::Choose => &["Enter"],
+            Self::Filter => &["/"],
+            Self::ClearFilter => &["Esc"],
+            Self::Sort => &["s"],
+            Self::RefreshInventory => &["i"],
+        }
+    }
+}
+
+/// Key bindings from the `[sssh.keys]` table. Each action is bound to a list of keys and actions
+/// that are not set use their default keys.
+///
+/// Keys are either a single character (`"q"`, `"/"`), or a named key (`"Up"`, `"Enter"`, `"Esc"`,
+/// `"Space"`, `"F1"`, ...), optionally prefixed with modifiers (`"ctrl-c"`, `"alt-x"`).
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigKeys {
+    pub up: Option<Vec<String>>,
+    pub down: Option<Vec<String>>,
+    pub left: Option<Vec<String>>,
+    pub right: Option<Vec<String>>,
+    pub quit: Option<Vec<String>>,
+    pub edit: Option<Vec<String>>,
+    pub add_server: Option<Vec<String>>,
+    pub edit_server: Option<Vec<String>>,
+    pub reload: Option<Vec<String>>,
+    pub choose: Option<Vec<String>>,
+    pub filter: Option<Vec<String>>,
+    pub clear_filter: Option<Vec<String>>,
+    pub sort: Option<Vec<String>>,
+    pub refresh_inventory: Option<Vec<String>>,
+}
+
+impl ConfigKeys {
+    fn get(&self, action: KeyAction) -> Option<Vec<String>> {
+        match action {
+            KeyAction::Up => self.up.clone(),
+            KeyAction::Down => self.down.clone(),
+            KeyAction::Left => self.left.clone(),
+            KeyAction::Right => self.right.clone(),
+            KeyAction::Quit => self.quit.clone(),
+            KeyAction::Edit => self.edit.clone(),
+            KeyAction::AddServer => self.add_server.clone(),
+            KeyAction::EditServer => self.edit_server.clone(),
+            KeyAction::Reload => self.reload.clone(),
+            KeyAction::Choose => self.choose.clone(),
+            KeyAction::Filter => self.filter.clone(),
+            KeyAction::ClearFilter => self.clear_filter.clone(),
+            KeyAction::Sort => self.sort.clone(),
+            KeyAction::RefreshInventory => self.refresh_inventory.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyBinding {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+    pub fn matches(&self, key: &KeyEvent) -> bool {
+        // Shift is already part of the character itself (e.g. `Q`):
+        let modifiers = key.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT);
+        let code = match key.code {
+            KeyCode::Char(character) if modifiers.contains(KeyModifiers::CONTROL) => {
+                KeyCode::Char(character.to_ascii_lowercase())
+            }
+            code => code,
+        };
+        self.code == code && self.modifiers == modifiers
+    }
+}
+
+impl TryFrom<&str> for KeyBinding {
+    type Error = String;
+
+    fn try_from(key: &str) -> Result<Self, Self::Error> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut name = key;
+        // `-` itself is a valid key:
+        while let Some((modifier, rest)) = name.split_once('-').filter(|(_, rest)| !rest.is_empty())
+        {
+            modifiers |= match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                _ => return Err(format!("unknown key modifier {:?} in {:?}", modifier, key)),
+            };
+            name = rest;
+        }
+        let mut character_iter = name.chars();
+        let code = match (character_iter.next(), character_iter.next()) {
+            (Some(character), None) if modifiers.contains(KeyModifiers::CONTROL) => {
+                KeyCode::Char(character.to_ascii_lowercase())
+            }
+            (Some(character), None) => KeyCode::Char(character),
+            _ => match name.to_lowercase().as_str() {
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "enter" | "return" => KeyCode::Enter,
+                "esc" | "escape" => KeyCode::Esc,
+                "backspace" => KeyCode::Backspace,
+                "tab" => KeyCode::Tab,
+                "space" => KeyCode::Char(' '),
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                "delete" => KeyCode::Delete,
+                "insert" => KeyCode::Insert,
+                function if function.starts_with('f') => function[1..]
+                    .parse::<u8>()
+                    .ok()
+                    .filter(|number| (1..=12).contains(number))
+                    .map(KeyCode::F)
+                    .ok_or_else(|| format!("unknown key {:?}", key))?,
+                _ => return Err(format!("unknown key {:?}", key)),
+            },
+        };
+        Ok(Self { code, modifiers })
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(formatter, "Ctrl-")?
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(formatter, "Alt-")?
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(formatter, "Space"),
+            KeyCode::Char(character) => write!(formatter, "{}", character),
+            KeyCode::F(number) => write!(formatter, "F{}", number),
+            KeyCode::PageUp => write!(formatter, "PageUp"),
+            KeyCode::PageDown => write!(formatter, "PageDown"),
+            code => write!(formatter, "{:?}", code),
+        }
+    }
+}
+
+/// Effective key bindings of the terminal UI.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyMap {
+    pub binding_list: Vec<(KeyAction, Vec<KeyBinding>)>,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        Self::try_from(&ConfigKeys::default()).unwrap()
+    }
+}
+
+impl TryFrom<&ConfigKeys> for KeyMap {
+    type Error = String;
+
+    fn try_from(config_keys: &ConfigKeys) -> Result<Self, Self::Error> {
+        let mut binding_list: Vec<(KeyAction, Vec<KeyBinding>)> = Vec::new();
+        for action in KeyAction::all() {
+            let key_list = config_keys.get(action).unwrap_or_else(|| {
+                action
+                    .default_key_list()
+                    .iter()
+                    .map(|key| key.to_string())
+                    .collect()
+            });
+            let mut action_binding_list = Vec::new();
+            for key in key_list {
+                let binding = KeyBinding::try_from(key.as_str())
+                    .map_err(|reason| format!("{} (action `{}`)", reason, action.name()))?;
+                if let Some((other_action, _)) = binding_list
+                    .iter()
+                    .find(|(_, other_binding_list)| other_binding_list.contains(&binding))
+                {
+                    return Err(format!(
+                        "key {:?} is bound to both `{}` and `{}` actions",
+                        key,
+                        other_action.name(),
+                        action.name()
+                    ));
+                }
+                action_binding_list.push(binding);
+            }
+            binding_list.push((action, action_binding_list));
+        }
+        Ok(Self { binding_list })
+    }
+}
+
+impl KeyMap {
+    pub fn action(&self, key: &KeyEvent) -> Option<KeyAction> {
+        self.binding_list
+            .iter()
+            .find(|(_, binding_list)| binding_list.iter().any(|binding| binding.matches(key)))
+            .map(|(action, _)| *action)
+    }
+
+    /// Human-readable keys of an action, e.g. `q/Ctrl-c`.
+    pub fn key_names(&self, action: KeyAction) -> String {
+        self.binding_list
+            .iter()
+            .find(|(other_action, _)| *other_action == action)
+            .map(|(_, binding_list)| {
+                binding_list
+                    .iter()
+                    .map(|binding| binding.to_string())
+                    .collect::<Vec<_>>()
+                    .join("/")
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn binding(code: KeyCode, modifiers: KeyModifiers) -> KeyBinding {
+        KeyBinding { code, modifiers }
+    }
+
+    #[test]
+    fn parse_keys() {
+        assert_eq!(
+            KeyBinding::try_from("q"),
+            Ok(binding(KeyCode::Char('q'), KeyModifiers::NONE))
+        );
+        assert_eq!(
+            KeyBinding::try_from("-"),
+            Ok(binding(KeyCode::Char('-'), KeyModifiers::NONE))
+        );
+        assert_eq!(
+            KeyBinding::try_from("PageDown"),
+            Ok(binding(KeyCode::PageDown, KeyModifiers::NONE))
+        );
+        assert_eq!(
+            KeyBinding::try_from("space"),
+            Ok(binding(KeyCode::Char(' '), KeyModifiers::NONE))
+        );
+        assert_eq!(
+            KeyBinding::try_from("F12"),
+            Ok(binding(KeyCode::F(12), KeyModifiers::NONE))
+        );
+    }
+
+    #[test]
+    fn parse_modifiers() {
+        assert_eq!(
+            KeyBinding::try_from("Ctrl-C"),
+            Ok(binding(KeyCode::Char('c'), KeyModifiers::CONTROL))
+        );
+        assert_eq!(
+            KeyBinding::try_from("ctrl-alt-Enter"),
+            Ok(binding(
+                KeyCode::Enter,
+                KeyModifiers::CONTROL | KeyModifiers::ALT
+            ))
+        );
+        assert_eq!(
+            KeyBinding::try_from("alt--"),
+            Ok(binding(KeyCode::Char('-'), KeyModifiers::ALT))
+        );
+    }
+
+    #[test]
+    fn parse_invalid_keys() {
+        assert!(KeyBinding::try_from("").is_err());
+        assert!(KeyBinding::try_from("F13").is_err());
+        assert!(KeyBinding::try_from("Fx").is_err());
+        assert!(KeyBinding::try_from("shift-a").is_err());
+        assert!(KeyBinding::try_from("unknown").is_err());
+    }
+
+    #[test]
+    fn display_keys() {
+        for key in ["q", "Ctrl-c", "Alt-Enter", "Space", "F1", "PageUp"] {
+            assert_eq!(KeyBinding::try_from(key).unwrap().to_string(), key);
+        }
+    }
+
+    #[test]
+    fn match_keys() {
+        let binding = KeyBinding::try_from("ctrl-c").unwrap();
+        assert!(binding.matches(&KeyEvent::new(KeyCode::Char('C'), KeyModifiers::CONTROL)));
+        assert!(binding.matches(&KeyEvent::new(
+            KeyCode::Char('c'),
+            KeyModifiers::CONTROL | KeyModifiers::SHIFT
+        )));
+        assert!(!binding.matches(&KeyEvent::new(KeyCode::Char('c'), KeyModifiers::NONE)));
+        let binding = KeyBinding::try_from("Q").unwrap();
+        assert!(binding.matches(&KeyEvent::new(KeyCode::Char('Q'), KeyModifiers::SHIFT)));
+    }
+
+    #[test]
+    fn keymap_overrides_defaults() {
+        let keymap = KeyMap::try_from(&ConfigKeys {
+            quit: Some(vec!["x".to_string(), "ctrl-c".to_string()]),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(keymap.key_names(KeyAction::Quit), "x/Ctrl-c");
+        assert_eq!(
+            keymap.action(&KeyEvent::new(KeyCode::Char('x'), KeyModifiers::NONE)),
+            Some(KeyAction::Quit)
+        );
+        assert_eq!(
+            keymap.action(&KeyEvent::new(KeyCode::Char('q'), KeyModifiers::NONE)),
+            None
+        );
+    }
+
+    #[test]
+    fn keymap_rejects_invalid_bindings() {
+        let error = KeyMap::try_from(&ConfigKeys {
+            sort: Some(vec!["q".to_string()]),
+            ..Default::default()
+        })
+        .unwrap_err();
+        assert!(error.contains("`quit` and `sort`"), "{}", error);
+        let error = KeyMap::try_from(&ConfigKeys {
+            up: Some(vec!["ctrl-".to_string()]),
+            ..Default::default()
+        })
+        .unwrap_err();
+        assert!(error.contains("(action `up`)"), "{}", error);
+    }
+}
@@ -1,10 +1,21 @@
+mod backup;
 mod error;
+mod expand;
+mod history;
+mod inventory;
+mod keymap;
 mod panic_hook;
 mod settings;
+mod ssh_config;
 mod subcommands;
+mod theme;
+mod trust;
 mod utils;
+mod validation;
 
 use std::io;
+use std::mem;
+use std::path::PathBuf;
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
 
@@ -14,6 +25,10 @@ use lazy_static::lazy_static;
 
 use crate::error::AppError;
 use crate::settings::{Settings, SubCommand};
+use subcommands::check::CheckFormat;
+use subcommands::export::Exports;
+use subcommands::import::Imports;
+use subcommands::list::ListFormat;
 use subcommands::sample::Samples;
 
 type SubCommandFunction = fn(&mut Settings) -> Result<(), AppError>;
@@ -24,14 +39,68 @@ lazy_static! {
             SubCommand::Select,
             subcommands::select::run as SubCommandFunction
         ),
+        (
+            SubCommand::Connect {
+                name: String::new(),
+                user: None
+            },
+            subcommands::connect::run as SubCommandFunction
+        ),
         (
             SubCommand::Edit,
             subcommands::edit::run as SubCommandFunction
         ),
         (
-            SubCommand::Config,
+            SubCommand::Add {
+                name: String::new(),
+                hostname: String::new(),
+                user_list: Vec::new(),
+                port: None,
+                description: None
+            },
+            subcommands::modify::run as SubCommandFunction
+        ),
+        (
+            SubCommand::Remove {
+                name: String::new()
+            },
+            subcommands::modify::run as SubCommandFunction
+        ),
+        (
+            SubCommand::Rename {
+                name: String::new(),
+                new_name: String::new()
+            },
+            subcommands::modify::run as SubCommandFunction
+        ),
+        (
+            SubCommand::Set {
+                name: String::new(),
+                field: String::new(),
+                value: None
+            },
+            subcommands::modify::run as SubCommandFunction
+        ),
+        (
+            SubCommand::Config {
+                effective: false,
+                backup: None
+            },
             subcommands::config::run as SubCommandFunction
         ),
+        (
+            SubCommand::Check {
+                format: CheckFormat::Text
+            },
+            subcommands::check::run as SubCommandFunction
+        ),
+        (
+            SubCommand::List {
+                format: ListFormat::Table,
+                field_list: Vec::new()
+            },
+            subcommands::list::run as SubCommandFunction
+        ),
         (
             SubCommand::Script,
             subcommands::script::run as SubCommandFunction
@@ -44,6 +113,23 @@ lazy_static! {
             SubCommand::Sample(Samples::Script),
             subcommands::sample::run as SubCommandFunction
         ),
+        (
+            SubCommand::Import(Imports::SshConfig {
+                file: PathBuf::new()
+            }),
+            subcommands::import::run as SubCommandFunction
+        ),
+        (
+            SubCommand::Export(Exports::SshConfig {
+                write: false,
+                file: PathBuf::new()
+            }),
+            subcommands::export::run as SubCommandFunction
+        ),
+        (
+            SubCommand::Trust { revoke: false },
+            subcommands::trust::run as SubCommandFunction
+        ),
     ]
     .to_vec();
 }
@@ -78,7 +164,8 @@ fn main() -> Result<()> {
         settings = settings::Settings::new()?;
     };
     for (subcommand, function) in CALLBACK_LIST.iter().cloned() {
-        if subcommand == settings.subcommand {
+        // Subcommands may carry their own arguments, so only compare variants:
+        if mem::discriminant(&subcommand) == mem::discriminant(&settings.subcommand) {
             if subcommand == SubCommand::Select {
                 if settings.verbose {
                     if atty::is(atty::Stream::Stderr) {
@@ -5,7 +5,7 @@ use std::{env, fmt::Write, mem, panic};
 pub fn setup() {
     panic::set_hook(Box::new(|panic_info| {
         let name = crate_name!();
-        let command = env::args().map(String::from).collect::<Vec<_>>().join(" ");
+        let command = env::args().collect::<Vec<_>>().join(" ");
         let command = if command.trim().is_empty() {
             String::new()
         } else {
@@ -1,17 +1,47 @@
 use crate::{
+    backup::{self, DEFAULT_BACKUP_COUNT},
     error::AppError,
+    expand::{self, InterpolationError},
+    inventory::{self, ConfigInventory},
+    keymap::{ConfigKeys, KeyMap},
+    subcommands::check::CheckFormat,
+    subcommands::config::Backups,
+    subcommands::export::Exports,
+    subcommands::import::Imports,
+    subcommands::list::{ListField, ListFormat},
     subcommands::sample::{Samples, DEFAULT_CONFIGURATION, DEFAULT_SCRIPT},
+    theme::{ConfigTheme, Theme},
+    trust,
 };
 use anyhow::{Context, Result};
 use clap::Parser;
-use dirs::config_dir;
+use dirs::{cache_dir, config_dir, data_dir, home_dir};
 use faccess::PathExt;
-use serde::Deserialize;
-use std::{collections::HashMap, fs, path::PathBuf};
-use tracing::{debug, info};
+use serde::{Deserialize, Deserializer};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process,
+};
+use tracing::{debug, info, warn};
 
 const DEFAULT_CONFIGURATION_FILENAME: &str = "sssh.toml";
 const DEFAULT_SCRIPT_FILENAME: &str = "sssh.sh";
+const DEFAULT_HISTORY_FILENAME: &str = "sssh.history";
+const DEFAULT_INVENTORY_CACHE_FILENAME: &str = "sssh.inventory.json";
+const DEFAULT_TRUST_DIRECTORY_NAME: &str = "sssh.trusted";
+#[cfg(target_family = "unix")]
+const DEFAULT_SYSTEM_CONFIGURATION_FILENAME: &str = "/etc/sssh/sssh.toml";
+#[cfg(not(target_family = "unix"))]
+const DEFAULT_SYSTEM_CONFIGURATION_FILENAME: &str = "C:\\ProgramData\\sssh\\sssh.toml";
+const PROJECT_CONFIGURATION_FILENAME: &str = ".sssh.toml";
+const DEFAULT_LAYERED_CONFIGURATION: &str = "# sssh configuration.
+# Servers and options of this file are merged with the system-wide and project-local
+# (`.sssh.toml`) configuration files.
+# Run `sssh config --effective` to see the result and `sssh sample config` for the format.
+";
 const DEFAULT_USERNAME: &str = "root";
 pub const DEFAULT_PORT_NUMBER: u16 = 22;
 const EDITOR_COMMAND_NOT_FOUND: &str = "<not found>";
@@ -63,6 +93,31 @@ pub struct Settings {
         default_value = default_configuration_filename(),
     )]
     pub configuration_file: PathBuf,
+    /// System-wide TOML configuration file that --config-file is layered on top of.
+    ///
+    /// Precedence of layers is: this file < ~/.config/sssh.toml < --config-file < .sssh.toml
+    #[arg(
+        name = "system-config-file",
+        long,
+        global = true,
+        env = "SSSH_SYSTEM_CONFIG_FILE",
+        default_value = DEFAULT_SYSTEM_CONFIGURATION_FILENAME,
+    )]
+    pub system_configuration_file: PathBuf,
+    /// Do not search current directory and its ancestors for a project-local `.sssh.toml`.
+    ///
+    /// The nearest `.sssh.toml` (below home directory) is layered on top of --config-file once
+    /// it's trusted with `sssh trust`.
+    #[arg(
+        name = "no-project-config",
+        short = 'P',
+        long,
+        global = true,
+        env = "SSSH_NO_PROJECT_CONFIG"
+    )]
+    pub no_project_configuration: bool,
+    #[arg(skip)]
+    pub project_configuration_file: Option<PathBuf>,
     /// An executable file that will accept SSH info to connect to chosen server.
     ///
     /// For more information run `sssh sample script`
@@ -77,6 +132,54 @@ pub struct Settings {
     pub script_file: PathBuf,
     #[arg(skip)]
     pub script: String,
+    /// File that keeps history of connections.
+    ///
+    /// It's used to sort servers by most recently or most frequently used and only keeps the last
+    /// 1000 connections.
+    #[arg(
+        name = "history-file",
+        short = 'H',
+        long,
+        global = true,
+        env = "SSSH_HISTORY_FILE",
+        default_value = default_history_filename(),
+    )]
+    pub history_file: PathBuf,
+    /// File that caches output of `[sssh.inventory]` command.
+    #[arg(
+        name = "inventory-cache-file",
+        long,
+        global = true,
+        env = "SSSH_INVENTORY_CACHE_FILE",
+        default_value = default_inventory_cache_filename(),
+    )]
+    pub inventory_cache_file: PathBuf,
+    /// Run `[sssh.inventory]` command even if its cached output is not expired yet.
+    #[arg(long, global = true, env = "SSSH_REFRESH_INVENTORY")]
+    pub refresh_inventory: bool,
+    /// Last loaded output of `[sssh.inventory]` command and the command itself.
+    #[arg(skip)]
+    pub inventory_output: Option<(Vec<String>, String)>,
+    /// Directory that keeps copies of project-local configuration files trusted by `sssh trust`.
+    #[arg(
+        name = "trust-directory",
+        long,
+        global = true,
+        env = "SSSH_TRUST_DIRECTORY",
+        default_value = default_trust_directory(),
+    )]
+    pub trust_directory: PathBuf,
+    /// TOML file that overrides `[sssh.theme]` table of configuration file.
+    ///
+    /// For more information run `sssh sample config`
+    #[arg(
+        name = "theme-file",
+        short = 't',
+        long,
+        global = true,
+        env = "SSSH_THEME_FILE"
+    )]
+    pub theme_file: Option<PathBuf>,
     /// Skip running final script.
     #[arg(short = 'S', long, global = true, env = "SSSH_SKIP_SELECT")]
     pub skip_select: bool,
@@ -108,25 +211,99 @@ pub struct Settings {
     pub configuration: Config,
 }
 
-#[derive(Debug, Clone, PartialEq, Parser)]
+#[derive(Debug, Clone, PartialEq, Default, Parser)]
 pub enum SubCommand {
     /// Select a server to connect from the terminal UI. (default)
+    #[default]
     Select,
+    /// Connect to a server without the terminal UI.
+    Connect {
+        /// Name of the server in configuration file.
+        name: String,
+        /// Username to connect with. (default: first username of the server)
+        #[arg(short, long)]
+        user: Option<String>,
+    },
     /// Edit configuration file to add/remove servers.
     Edit,
-    /// Print current configuration file contents.
-    Config,
+    /// Add a server to configuration file.
+    Add {
+        /// Name of the new server. A dotted name (e.g. `prod.web1`) adds it to a group.
+        name: String,
+        /// Hostname of the new server.
+        hostname: String,
+        /// Username of the new server. It can be given more than once.
+        #[arg(short, long = "user")]
+        user_list: Vec<String>,
+        /// Port of the new server.
+        #[arg(short, long)]
+        port: Option<u16>,
+        /// Description of the new server.
+        #[arg(short, long)]
+        description: Option<String>,
+    },
+    /// Remove a server from configuration file.
+    Remove {
+        /// Name of the server in configuration file.
+        name: String,
+    },
+    /// Rename a server in configuration file.
+    Rename {
+        /// Name of the server in configuration file.
+        name: String,
+        /// New name of the server.
+        new_name: String,
+    },
+    /// Set a field of a server in configuration file.
+    Set {
+        /// Name of the server in configuration file.
+        name: String,
+        /// Field of the server, e.g. `port`, `users` or `ssh_options.<KEY>`.
+        field: String,
+        /// New value. Lists (e.g. `users`) are comma-separated. Without a value, the field is
+        /// removed, so its default value is used.
+        value: Option<String>,
+    },
+    /// Print current configuration file contents, or list, diff and restore its backups.
+    Config {
+        /// Print merged configuration of all layers and which file each value comes from.
+        #[arg(long)]
+        effective: bool,
+        #[command(subcommand)]
+        backup: Option<Backups>,
+    },
+    /// Check configuration files and report every problem with its position.
+    Check {
+        /// Output format.
+        #[arg(short, long, value_enum, default_value_t)]
+        format: CheckFormat,
+    },
+    /// Print servers with their default values applied.
+    List {
+        /// Output format.
+        #[arg(short, long, value_enum, default_value_t)]
+        format: ListFormat,
+        /// Comma-separated list of fields to print. (default: all fields)
+        #[arg(name = "field", short = 'F', long, value_enum, value_delimiter = ',')]
+        field_list: Vec<ListField>,
+    },
     /// Print current script file contents.
     Script,
     /// Samples for configuration and script.
     #[command(subcommand)]
     Sample(Samples),
-}
-
-impl Default for SubCommand {
-    fn default() -> Self {
-        Self::Select
-    }
+    /// Import servers from other tools into configuration file.
+    #[command(subcommand)]
+    Import(Imports),
+    /// Export servers for other tools.
+    #[command(subcommand)]
+    Export(Exports),
+    /// Trust project-local `.sssh.toml` of current directory, so it's used until it changes.
+    Trust {
+        /// Stop trusting it instead.
+        #[arg(long)]
+        revoke: bool,
+    },
 }
 
 #[derive(Debug, Clone, Default, Deserialize)]
@@ -135,17 +312,226 @@ pub struct Config {
     pub sssh: ConfigApp,
     #[serde(skip)]
     pub raw: String,
-    #[serde(flatten)]
+    #[serde(skip)]
+    pub keymap: KeyMap,
+    #[serde(skip)]
+    pub theme: Theme,
+    /// Servers by their dotted path, e.g. `prod.eu.web1`.
+    #[serde(skip)]
     pub servers: HashMap<String, ConfigServer>,
+    /// Files included via `include` option and their contents.
+    #[serde(skip)]
+    pub include_list: Vec<(PathBuf, String)>,
+    /// Configuration files that are merged into this one (lowest precedence first).
+    #[serde(skip)]
+    pub layer_list: Vec<PathBuf>,
+    /// Merged tables of all layers.
+    #[serde(skip)]
+    pub effective: toml::Table,
+    /// Configuration file that each value comes from, by dotted path of its key.
+    #[serde(skip)]
+    pub origin_map: BTreeMap<String, PathBuf>,
+    /// Server and group tables as they are in configuration file.
+    #[serde(flatten)]
+    table: toml::Table,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ConfigApp {
+    /// Other files (or glob patterns of them) that contain more servers.
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(default)]
+    pub sort: SortMode,
+    #[serde(default)]
+    pub keys: ConfigKeys,
+    #[serde(default)]
+    pub theme: ConfigTheme,
+    #[serde(default)]
+    pub inventory: ConfigInventory,
+    /// Abstract servers that are not listed but other servers can extend, by their name.
+    #[serde(default)]
+    pub templates: toml::Table,
+    #[serde(default)]
+    pub defaults: ConfigDefaults,
+    /// `[[sssh.match]]` rules that set fields of matching servers, in order.
+    #[serde(default, rename = "match")]
+    pub match_list: Vec<ConfigMatch>,
+    /// Number of backups of configuration file to keep (0 disables backups).
+    pub backup_count: Option<usize>,
+    /// Replace environment variables (e.g. `${USER}`) in string values of servers. It's opt-in, so
+    /// `${...}` in values of older configuration files is kept as it is.
+    #[serde(default)]
+    pub interpolate: bool,
+}
+
+impl ConfigApp {
+    pub fn backup_count(&self) -> usize {
+        self.backup_count.unwrap_or(DEFAULT_BACKUP_COUNT)
+    }
+
+    /// Fields that `server` has unless it sets them itself (or inherits them from a template):
+    /// for each field, the first matching rule that sets it wins over later rules and over
+    /// `[sssh.defaults]`.
+    pub fn server_defaults(&self, server: &ConfigServer) -> toml::Table {
+        let mut table = self.defaults.server.clone();
+        self.match_list
+            .iter()
+            .rev()
+            .filter(|rule| rule.matches(server))
+            .for_each(|rule| override_table(&mut table, &rule.set));
+        table
+    }
 }
 
+/// `[sssh.defaults]` table: fields that servers (including inventory servers) have unless they
+/// set them themselves or inherit them from a template.
 #[derive(Debug, Clone, Default, Deserialize)]
-pub struct ConfigApp {}
+pub struct ConfigDefaults {
+    /// Log in as the local user (`$USER`) to servers without any username, instead of `root`.
+    #[serde(default)]
+    pub local_user: bool,
+    /// Any server field except `hostname`, `group` and `extends`, e.g. `users` or `port`.
+    #[serde(flatten)]
+    pub server: toml::Table,
+}
+
+impl ConfigDefaults {
+    /// Username of servers that neither they nor `[sssh.defaults]` set `users` of.
+    fn username(&self) -> String {
+        if self.local_user {
+            if let Some(username) = ["USER", "USERNAME"]
+                .iter()
+                .find_map(|name| std::env::var(name).ok())
+                .filter(|username| !username.is_empty())
+            {
+                return username;
+            }
+            warn!("Could not find local username, use `{}`", DEFAULT_USERNAME);
+        }
+        DEFAULT_USERNAME.to_string()
+    }
+
+    fn try_validate(&self, filename: PathBuf) -> Result<(), AppError> {
+        try_validate_server_fields(&self.server, "sssh.defaults", filename)
+    }
+}
+
+/// A `[[sssh.match]]` rule, e.g. servers with `hostname = "*.prod.example.com"` use
+/// `set = { port = 2222, users = ["deploy"] }`. A rule matches if all of its conditions match.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigMatch {
+    /// Glob pattern of dotted server name, e.g. `prod.*`.
+    pub name: Option<String>,
+    /// Glob pattern of hostname, e.g. `*.prod.example.com`.
+    pub hostname: Option<String>,
+    /// One of `tags` of server.
+    pub tag: Option<String>,
+    /// Server fields that matching servers have.
+    #[serde(default)]
+    pub set: toml::Table,
+}
+
+impl ConfigMatch {
+    pub fn matches(&self, server: &ConfigServer) -> bool {
+        let glob_matches = |maybe_pattern: &Option<String>, value: &str| {
+            maybe_pattern.as_ref().is_none_or(|pattern| {
+                glob::Pattern::new(pattern).is_ok_and(|pattern| pattern.matches(value))
+            })
+        };
+        glob_matches(&self.name, &server.name)
+            && glob_matches(&self.hostname, &server.hostname)
+            && self
+                .tag
+                .as_ref()
+                .is_none_or(|tag| server.tag_list.contains(tag))
+    }
+
+    fn try_validate(&self, index: usize, filename: PathBuf) -> Result<(), AppError> {
+        let name = format!("sssh.match[{}]", index);
+        let match_error = |reason: String| AppError::ConfigEntry {
+            filename: filename.clone(),
+            name: name.clone(),
+            reason,
+        };
+        if self.name.is_none() && self.hostname.is_none() && self.tag.is_none() {
+            return Err(match_error(
+                "rule needs at least one of `name`, `hostname` and `tag`".to_string(),
+            ));
+        }
+        for pattern in [&self.name, &self.hostname].into_iter().flatten() {
+            glob::Pattern::new(pattern).map_err(|error| {
+                match_error(format!("invalid pattern {:?}: {}", pattern, error))
+            })?;
+        }
+        try_validate_server_fields(&self.set, &name, filename.clone())
+    }
+}
+
+/// Checks that `table` only contains server fields of the right types and none of the fields
+/// that identify a server (`hostname`, `group` and `extends`).
+fn try_validate_server_fields(
+    table: &toml::Table,
+    name: &str,
+    filename: PathBuf,
+) -> Result<(), AppError> {
+    let entry_error = |reason: String| AppError::ConfigEntry {
+        filename: filename.clone(),
+        name: name.to_string(),
+        reason,
+    };
+    if let Some(key) = ["hostname", "group", "extends"]
+        .iter()
+        .find(|key| table.contains_key(**key))
+    {
+        return Err(entry_error(format!("`{}` can not be set here", key)));
+    }
+    let mut table = table.clone();
+    table.insert("hostname".to_string(), toml::Value::from(""));
+    toml::Value::Table(table)
+        .try_into::<ConfigServer>()
+        .map(|_| ())
+        .map_err(|error| entry_error(error.to_string().trim().replace('\n', " ")))
+}
+
+/// Order of servers in the terminal UI.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortMode {
+    /// Alphabetical order of server names.
+    #[default]
+    Name,
+    /// Most recently used servers first.
+    Recent,
+    /// Most frequently used servers first.
+    Frequent,
+}
+
+impl SortMode {
+    pub fn next(&self) -> Self {
+        match self {
+            Self::Name => Self::Recent,
+            Self::Recent => Self::Frequent,
+            Self::Frequent => Self::Name,
+        }
+    }
+}
 
 #[derive(Debug, Clone, Default, Deserialize)]
 pub struct ConfigServer {
     #[serde(skip)]
     pub name: String,
+    /// Configuration file that server is defined in.
+    #[serde(skip)]
+    pub filename: PathBuf,
+    /// Keys of server table in its configuration file, e.g. `["prod", "web"]`. It's empty for
+    /// inventory servers.
+    #[serde(skip)]
+    pub table_path: Vec<String>,
+    /// Dotted path of the group that server belongs to, e.g. `prod.eu`.
+    #[serde(default)]
+    pub group: String,
     #[serde(alias = "users", default)]
     pub username_list: Vec<String>,
     pub hostname: String,
@@ -153,6 +539,17 @@ pub struct ConfigServer {
     pub port: u16,
     #[serde(default)]
     pub description: String,
+    /// Private key file, e.g. `~/.ssh/id_ed25519`.
+    pub identity_file: Option<String>,
+    /// Jump host(s) to connect through, e.g. `bastion` or `user@bastion:2222`.
+    pub proxy_jump: Option<String>,
+    /// Other OpenSSH client options, e.g. `{ ServerAliveInterval = 30, Compression = true }`.
+    #[serde(default, deserialize_with = "deserialize_ssh_options")]
+    pub ssh_options: BTreeMap<String, String>,
+    pub forward_agent: Option<bool>,
+    /// Free-form labels that `[[sssh.match]]` rules can match, e.g. `["db", "prod"]`.
+    #[serde(alias = "tags", default)]
+    pub tag_list: Vec<String>,
 }
 
 impl Settings {
@@ -174,21 +571,194 @@ impl Settings {
         Ok(settings)
     }
 
+    /// Loads configuration without running `[sssh.inventory]` command. Its servers are only added
+    /// if it's already loaded by [`Self::try_load_and_set_servers`] (e.g. in the terminal UI).
     pub fn try_load_and_set_configuration(&mut self) -> Result<Config, AppError> {
-        self.maybe_try_create_configuration_file()?;
-        let config = Config::try_from(self.configuration_file.clone())?;
+        self.try_load(false)
+    }
+
+    /// Loads configuration and servers of `[sssh.inventory]` command, for subcommands that need
+    /// all servers.
+    pub fn try_load_and_set_servers(&mut self) -> Result<Config, AppError> {
+        self.try_load(true)
+    }
+
+    fn try_load(&mut self, run_inventory: bool) -> Result<Config, AppError> {
+        let layer_list = self.try_read_configuration()?;
+        let mut config = Config::try_parse_layers(layer_list, &self.configuration_file)?;
+        if let Some(theme_filename) = self.theme_file.clone() {
+            config.theme = try_load_theme_file(theme_filename, &config.sssh.theme)?;
+        }
+        self.try_load_inventory(&mut config, run_inventory)?;
         self.configuration = config.clone();
         Ok(config)
     }
 
+    /// Finds project-local configuration file, creates configuration file if it doesn't exist
+    /// and reads all configuration layers (lowest precedence first).
+    pub fn try_read_configuration(&mut self) -> Result<Vec<(PathBuf, String)>, AppError> {
+        self.project_configuration_file = if self.no_project_configuration {
+            None
+        } else {
+            find_project_configuration_file().filter(|filename| {
+                fs::read_to_string(filename)
+                    .is_ok_and(|configuration| self.is_trusted(filename, &configuration))
+            })
+        };
+        self.maybe_try_create_configuration_file()?;
+        let configuration =
+            fs::read_to_string(self.configuration_file.clone()).map_err(|error| {
+                AppError::FileRead {
+                    title: "configuration",
+                    filename: self.configuration_file.clone(),
+                    source: error,
+                }
+            })?;
+        self.try_read_configuration_layers(configuration)
+    }
+
+    /// Adds servers of `[sssh.inventory]` command. Without `run`, the command is not run and its
+    /// last output is used (if the command is the same).
+    fn try_load_inventory(&mut self, config: &mut Config, run: bool) -> Result<(), AppError> {
+        let command = config.sssh.inventory.command.clone();
+        if command.is_empty() {
+            return Ok(());
+        }
+        let filename = origin(
+            &config.origin_map,
+            "sssh.inventory",
+            &self.configuration_file,
+        );
+        // Only system-wide, user and --config-file configuration files can run commands:
+        if self.project_configuration_file.as_ref() == Some(&filename) {
+            warn!(filename = ?filename, "Ignored `[sssh.inventory]` of project configuration file");
+            return Ok(());
+        }
+        let output = if run {
+            inventory::try_load(
+                &config.sssh.inventory,
+                self.inventory_cache_file.clone(),
+                self.refresh_inventory,
+            )?
+        } else {
+            match self.inventory_output.clone() {
+                Some((last_command, output)) if last_command == command => output,
+                _ => {
+                    debug!("Inventory is not loaded, skipped its servers");
+                    return Ok(());
+                }
+            }
+        };
+        self.inventory_output = Some((command.clone(), output.clone()));
+        let server_list = inventory::parse(&command, &output, &config.sssh)?;
+        config.add_inventory_servers(server_list, filename);
+        Ok(())
+    }
+
     pub fn maybe_try_create_configuration_file(&mut self) -> Result<(), AppError> {
         let configuration_filename = self.configuration_file.clone();
         if !configuration_filename.exists() {
+            // Sample servers should not be added on top of other layers:
+            if !self.lower_configuration_layer_list().is_empty()
+                || self.project_configuration_file.is_some()
+            {
+                fs::write(
+                    configuration_filename.clone(),
+                    DEFAULT_LAYERED_CONFIGURATION,
+                )
+                .map_err(|error| AppError::FileWrite {
+                    title: "configuration",
+                    filename: configuration_filename.clone(),
+                    source: error,
+                })?;
+                info!(configuration_file = ?configuration_filename, "Created configuration file");
+                return Ok(());
+            }
             let _ = self.try_create_default_configuration_file()?;
         }
         Ok(())
     }
 
+    /// Existing configuration files that --config-file is layered on top of (lowest precedence
+    /// first).
+    pub fn lower_configuration_layer_list(&self) -> Vec<PathBuf> {
+        let mut layer_list = Vec::new();
+        for filename in [
+            self.system_configuration_file.clone(),
+            PathBuf::from(default_configuration_filename()),
+        ] {
+            if filename != self.configuration_file
+                && filename.exists()
+                && !layer_list.contains(&filename)
+            {
+                layer_list.push(filename)
+            }
+        }
+        layer_list
+    }
+
+    /// Files that configuration is loaded from: all layers, included files and the theme file.
+    pub fn configuration_file_list(&self) -> Vec<PathBuf> {
+        let mut file_list = self.lower_configuration_layer_list();
+        file_list.push(self.configuration_file.clone());
+        file_list.extend(self.project_configuration_file.clone());
+        file_list.extend(
+            self.configuration
+                .include_list
+                .iter()
+                .map(|(filename, _)| filename.clone()),
+        );
+        file_list.extend(self.theme_file.clone());
+        file_list
+    }
+
+    /// Reads all configuration layers (lowest precedence first) with `configuration` as contents
+    /// of --config-file. Project-local configuration file takes precedence over --config-file.
+    pub fn try_read_configuration_layers(
+        &self,
+        configuration: String,
+    ) -> Result<Vec<(PathBuf, String)>, AppError> {
+        let read = |filename: PathBuf| {
+            debug!(filename = ?filename, "Load configuration layer");
+            fs::read_to_string(filename.clone())
+                .map(|configuration| (filename.clone(), configuration))
+                .map_err(|error| AppError::FileRead {
+                    title: "configuration",
+                    filename,
+                    source: error,
+                })
+        };
+        let mut layer_list = self
+            .lower_configuration_layer_list()
+            .into_iter()
+            .map(read)
+            .collect::<Result<Vec<_>, _>>()?;
+        layer_list.push((self.configuration_file.clone(), configuration));
+        if let Some(filename) = self.project_configuration_file.clone() {
+            if filename != self.configuration_file {
+                let (filename, configuration) = read(filename)?;
+                // It may have changed since it was found:
+                if self.is_trusted(&filename, &configuration) {
+                    layer_list.push((filename, configuration));
+                }
+            }
+        }
+        Ok(layer_list)
+    }
+
+    /// Project-local configuration file can run commands (e.g. with a `ProxyCommand` SSH option)
+    /// and change servers of other layers, so it's only used after the user trusts it.
+    fn is_trusted(&self, filename: &Path, configuration: &str) -> bool {
+        if trust::is_trusted(&self.trust_directory, filename, configuration) {
+            return true;
+        }
+        warn!(
+            filename = ?filename,
+            "Ignored project configuration file since it's not trusted or it has changed since it was trusted, run `sssh trust` to use it"
+        );
+        false
+    }
+
     pub fn maybe_try_create_script_file(&mut self) -> Result<String, AppError> {
         let script_filename = self.script_file.clone();
         let script = DEFAULT_SCRIPT.to_string();
@@ -225,6 +795,33 @@ impl Settings {
         Ok(())
     }
 
+    pub fn try_write_configuration(&mut self, configuration: String) -> Result<Config, AppError> {
+        // Never replace a working configuration file with a broken one:
+        let layer_list = self.try_read_configuration_layers(configuration.clone())?;
+        let mut config = Config::try_parse_layers(layer_list, &self.configuration_file)?;
+        if let Some(theme_filename) = self.theme_file.clone() {
+            config.theme = try_load_theme_file(theme_filename, &config.sssh.theme)?;
+        }
+        backup::try_save(&self.configuration_file, config.sssh.backup_count())?;
+        write_atomically(&self.configuration_file, &configuration).map_err(|error| {
+            AppError::FileWrite {
+                title: "configuration",
+                filename: self.configuration_file.clone(),
+                source: error,
+            }
+        })?;
+        info!(
+            configuration_file = ?self.configuration_file,
+            "Updated configuration file"
+        );
+        // Configuration file is already written, so inventory can not fail the write:
+        if let Err(error) = self.try_load_inventory(&mut config, false) {
+            warn!(error = ?error, "Could not load inventory");
+        }
+        self.configuration = config.clone();
+        Ok(config)
+    }
+
     pub fn try_create_default_configuration_file(&mut self) -> Result<Config, AppError> {
         self.configuration = Config::new(self.configuration_file.clone())?;
         Ok(self.configuration.clone())
@@ -297,6 +894,66 @@ impl Settings {
     }
 }
 
+/// Hidden file next to `filename` (so it can be renamed to it), e.g. `.sssh.edit.toml` for
+/// `sssh.toml` and `edit`.
+pub fn sibling_filename(filename: &Path, tag: &str) -> PathBuf {
+    let stem = filename
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = filename
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_string())
+        .unwrap_or_else(|| "toml".to_string());
+    filename.with_file_name(format!(
+        ".{}.{}.{}",
+        stem.trim_start_matches('.'),
+        tag,
+        extension
+    ))
+}
+
+/// Writes `contents` to a temporary file and renames it to `filename`, so `filename` is either
+/// the old or the new file even if writing fails halfway. Symbolic links are followed.
+pub fn write_atomically(filename: &Path, contents: &str) -> io::Result<()> {
+    let filename = fs::canonicalize(filename).unwrap_or_else(|_| filename.to_path_buf());
+    let temporary_filename = sibling_filename(&filename, &format!("write-{}", process::id()));
+    write_copy(&temporary_filename, contents, &filename)
+        .and_then(|_| fs::rename(&temporary_filename, &filename))
+        .inspect_err(|_| {
+            let _ = fs::remove_file(&temporary_filename);
+        })
+}
+
+/// Writes `contents` (e.g. a copy of `original`) to `filename` with the same permissions as
+/// `original`. They're set before anything is written, so a copy of a private configuration file
+/// is never readable by others.
+pub fn write_copy(filename: &Path, contents: &str, original: &Path) -> io::Result<()> {
+    let maybe_permissions = fs::metadata(original)
+        .ok()
+        .map(|metadata| metadata.permissions());
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    if let Some(permissions) = &maybe_permissions {
+        set_creation_permissions(&mut options, permissions);
+    }
+    let mut file = options.open(filename)?;
+    // `filename` may already exist with other permissions:
+    if let Some(permissions) = maybe_permissions {
+        file.set_permissions(permissions)?;
+    }
+    file.write_all(contents.as_bytes())
+}
+
+#[cfg(target_family = "unix")]
+fn set_creation_permissions(options: &mut OpenOptions, permissions: &fs::Permissions) {
+    use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+    options.mode(permissions.mode() & 0o777);
+}
+
+#[cfg(not(target_family = "unix"))]
+fn set_creation_permissions(_options: &mut OpenOptions, _permissions: &fs::Permissions) {}
+
 #[cfg(target_family = "unix")]
 fn set_permissions(filename: PathBuf) -> Result<(), AppError> {
     use std::os::unix::fs::PermissionsExt;
@@ -324,27 +981,205 @@ impl TryFrom<PathBuf> for Config {
                 filename: filename.clone(),
                 source: error,
             })?;
-        let mut config: Config =
-            toml::from_str(&configuration).map_err(|error| AppError::ConfigSyntax {
-                filename: filename.clone(),
-                source: error,
+        Self::try_parse(filename, configuration)
+    }
+}
+
+impl Config {
+    pub fn try_parse(filename: PathBuf, configuration: String) -> Result<Self, AppError> {
+        Self::try_parse_layers([(filename.clone(), configuration)].to_vec(), &filename)
+    }
+
+    /// Merges configuration layers (lowest precedence first) into one configuration.
+    ///
+    /// Tables are merged key by key and any other value (including arrays) of a layer replaces the
+    /// one from lower layers, so a layer can e.g. only change `users` of a server. `filename` is
+    /// the layer of configuration file itself that `raw` contains.
+    pub fn try_parse_layers(
+        layer_list: Vec<(PathBuf, String)>,
+        filename: &Path,
+    ) -> Result<Self, AppError> {
+        Self::parse_layers(layer_list, filename).map_err(|mut error_list| error_list.remove(0))
+    }
+
+    /// Same as [`Config::try_parse_layers`], but it returns every error that it finds (in the
+    /// order of the files) instead of only the first one.
+    pub fn parse_layers(
+        layer_list: Vec<(PathBuf, String)>,
+        filename: &Path,
+    ) -> Result<Self, Vec<AppError>> {
+        let filename = filename.to_path_buf();
+        let configuration = layer_list
+            .iter()
+            .find(|(layer_filename, _)| *layer_filename == filename)
+            .map(|(_, configuration)| configuration.clone())
+            .unwrap_or_default();
+        let mut error_list = Vec::new();
+        let mut table = toml::Table::new();
+        let mut origin_map = BTreeMap::new();
+        for (layer_filename, layer) in layer_list.iter() {
+            // Check types of each layer on its own to have line numbers in errors:
+            if let Err(error) = toml::from_str::<Config>(layer) {
+                error_list.push(AppError::ConfigSyntax {
+                    filename: layer_filename.clone(),
+                    source: error,
+                });
+                continue;
+            }
+            let mut layer_table: toml::Table = toml::from_str(layer).unwrap_or_default();
+            make_include_absolute(layer_filename, &mut layer_table);
+            merge_table(
+                &mut table,
+                layer_table,
+                layer_filename,
+                String::new(),
+                &mut origin_map,
+            );
+        }
+        if !error_list.is_empty() {
+            return Err(error_list);
+        }
+        let mut config: Config = toml::Value::Table(table.clone())
+            .try_into()
+            .map_err(|error| {
+                vec![AppError::ConfigSyntax {
+                    filename: filename.clone(),
+                    source: error,
+                }]
             })?;
-        config.servers.iter_mut().for_each(|(name, server)| {
-            server.name = name.clone();
-            if server.username_list.is_empty() {
-                debug!(
-                    server_name = name,
-                    "Use default username `{}` for server", DEFAULT_USERNAME
-                );
-                server.username_list.push(DEFAULT_USERNAME.to_string());
+        config.layer_list = layer_list
+            .iter()
+            .map(|(layer_filename, _)| layer_filename.clone())
+            .collect();
+        config.effective = table;
+        config.origin_map = origin_map;
+        let defaults_filename = origin(&config.origin_map, "sssh.defaults", &filename);
+        if let Err(error) = config.sssh.defaults.try_validate(defaults_filename.clone()) {
+            error_list.push(error);
+        }
+        let interpolate = config.sssh.interpolate;
+        if interpolate {
+            if let Err(error) = try_interpolate_table(
+                &mut config.sssh.defaults.server,
+                "sssh.defaults",
+                defaults_filename,
+            ) {
+                error_list.push(error);
             }
-        });
+        }
+        for (index, rule) in config.sssh.match_list.iter_mut().enumerate() {
+            let rule_filename = origin(&config.origin_map, "sssh.match", &filename);
+            if let Err(error) = rule.try_validate(index, rule_filename.clone()) {
+                error_list.push(error);
+            }
+            if interpolate {
+                if let Err(error) = try_interpolate_table(
+                    &mut rule.set,
+                    &format!("sssh.match[{}]", index),
+                    rule_filename,
+                ) {
+                    error_list.push(error);
+                }
+            }
+        }
+        collect_servers(
+            &ServerSource {
+                filename: &filename,
+                origin_map: &config.origin_map,
+                sssh: &config.sssh,
+                root: &config.table,
+            },
+            &config.table,
+            Vec::new(),
+            &mut config.servers,
+            &mut error_list,
+        );
+        let include_filename_list = include_filename_list(&filename, &config.sssh.include)
+            .unwrap_or_else(|error| {
+                error_list.push(error);
+                Vec::new()
+            });
+        for include_filename in include_filename_list {
+            if include_filename == filename
+                || config
+                    .include_list
+                    .iter()
+                    .any(|(other_filename, _)| *other_filename == include_filename)
+            {
+                debug!(filename = ?include_filename, "Skip file that is already included");
+                continue;
+            }
+            let include = match fs::read_to_string(include_filename.clone()) {
+                Ok(include) => include,
+                Err(error) => {
+                    error_list.push(AppError::ConfigInclude {
+                        filename: filename.clone(),
+                        pattern: include_filename.to_string_lossy().to_string(),
+                        reason: error.to_string(),
+                    });
+                    continue;
+                }
+            };
+            let table: toml::Table = match toml::from_str(&include) {
+                Ok(table) => table,
+                Err(error) => {
+                    error_list.push(AppError::ConfigSyntax {
+                        filename: include_filename,
+                        source: error,
+                    });
+                    continue;
+                }
+            };
+            if table.contains_key("sssh") {
+                error_list.push(AppError::ConfigEntry {
+                    filename: include_filename,
+                    name: "sssh".to_string(),
+                    reason: "options of sssh can only be set in main configuration file"
+                        .to_string(),
+                });
+                continue;
+            }
+            collect_servers(
+                &ServerSource {
+                    filename: &include_filename,
+                    origin_map: &BTreeMap::new(),
+                    sssh: &config.sssh,
+                    root: &table,
+                },
+                &table,
+                Vec::new(),
+                &mut config.servers,
+                &mut error_list,
+            );
+            debug!(filename = ?include_filename, "Included configuration file");
+            config.include_list.push((include_filename, include));
+        }
+        let username = config.sssh.defaults.username();
+        config
+            .servers
+            .values_mut()
+            .for_each(|server| set_default_username(server, &username));
+        match KeyMap::try_from(&config.sssh.keys) {
+            Ok(keymap) => config.keymap = keymap,
+            Err(reason) => error_list.push(AppError::ConfigKeyBinding {
+                filename: origin(&config.origin_map, "sssh.keys", &filename),
+                reason,
+            }),
+        }
+        match Theme::try_from(&config.sssh.theme) {
+            Ok(theme) => config.theme = theme,
+            Err(reason) => error_list.push(AppError::ConfigTheme {
+                filename: origin(&config.origin_map, "sssh.theme", &filename),
+                reason,
+            }),
+        }
+        if !error_list.is_empty() {
+            return Err(error_list);
+        }
         config.raw = configuration;
         Ok(config)
     }
-}
 
-impl Config {
     pub fn new(filename: PathBuf) -> Result<Self, AppError> {
         if filename.exists() {
             return Err(AppError::FileAlreadyExists {
@@ -369,6 +1204,522 @@ impl Config {
     pub fn is_default_servers(&self) -> bool {
         self.raw.trim() == DEFAULT_CONFIGURATION.trim()
     }
+
+    /// Servers of configuration files take precedence over inventory servers with the same name.
+    pub fn add_inventory_servers(&mut self, server_list: Vec<ConfigServer>, filename: PathBuf) {
+        let username = self.sssh.defaults.username();
+        for mut server in server_list {
+            if self.servers.contains_key(&server.name) {
+                debug!(
+                    server_name = server.name,
+                    "Server of configuration file overrides inventory server"
+                );
+                continue;
+            }
+            server.filename = filename.clone();
+            set_default_username(&mut server, &username);
+            self.servers.insert(server.name.clone(), server);
+        }
+    }
+}
+
+fn set_default_username(server: &mut ConfigServer, username: &str) {
+    if server.username_list.is_empty() {
+        debug!(
+            server_name = server.name,
+            "Use default username `{}` for server", username
+        );
+        server.username_list.push(username.to_string());
+    }
+}
+
+/// Where servers of a table come from and what they can extend.
+struct ServerSource<'a> {
+    filename: &'a PathBuf,
+    origin_map: &'a BTreeMap<String, PathBuf>,
+    /// Options of sssh, for `[sssh.templates]`, `[sssh.defaults]` and `[[sssh.match]]`.
+    sssh: &'a ConfigApp,
+    /// Top-level table of the file, to find servers that other servers extend.
+    root: &'a toml::Table,
+}
+
+impl ServerSource<'_> {
+    fn origin(&self, path: &str) -> PathBuf {
+        origin(self.origin_map, path, self.filename)
+    }
+
+    /// Resolves `extends` chain of a server (or template) table. Fields of the table take
+    /// precedence over the fields of its parent and `extends` names a template or a server.
+    fn try_inherit(
+        &self,
+        table: &toml::Table,
+        chain: &mut Vec<String>,
+    ) -> Result<toml::Table, AppError> {
+        let name = chain.last().cloned().unwrap_or_default();
+        let parent_name = match table.get("extends") {
+            None => return Ok(table.clone()),
+            Some(toml::Value::String(parent_name)) => parent_name.clone(),
+            Some(_) => {
+                return Err(AppError::ConfigEntry {
+                    filename: self.origin(&format!("{}.extends", name)),
+                    name,
+                    reason: "`extends` must be name of a template or a server".to_string(),
+                })
+            }
+        };
+        if chain.contains(&parent_name) {
+            chain.push(parent_name);
+            return Err(AppError::ConfigTemplateCycle {
+                filename: self.origin(&format!("{}.extends", name)),
+                chain: chain.clone(),
+            });
+        }
+        let parent_table = match self.sssh.templates.get(&parent_name) {
+            Some(value) => value.as_table(),
+            None => parent_name.split('.').try_fold(self.root, |table, key| {
+                table.get(key).and_then(|value| value.as_table())
+            }),
+        }
+        .ok_or_else(|| AppError::ConfigTemplateNotFound {
+            filename: self.origin(&format!("{}.extends", name)),
+            name: name.clone(),
+            template: parent_name.clone(),
+        })?;
+        chain.push(parent_name);
+        let mut inherited = self.try_inherit(parent_table, chain)?;
+        override_table(&mut inherited, table);
+        inherited.remove("extends");
+        Ok(inherited)
+    }
+}
+
+/// Interpolates environment variables of all strings in `table` (see [`expand::interpolate`]).
+fn try_interpolate_table(
+    table: &mut toml::Table,
+    name: &str,
+    filename: PathBuf,
+) -> Result<(), AppError> {
+    fn interpolate_value(value: &mut toml::Value) -> Result<(), InterpolationError> {
+        match value {
+            toml::Value::String(string) => *string = expand::interpolate(string)?,
+            toml::Value::Array(array) => array.iter_mut().try_for_each(interpolate_value)?,
+            toml::Value::Table(table) => table
+                .iter_mut()
+                .try_for_each(|(_, value)| interpolate_value(value))?,
+            _ => (),
+        }
+        Ok(())
+    }
+    table
+        .iter_mut()
+        .try_for_each(|(_, value)| interpolate_value(value))
+        .map_err(|error| match error {
+            InterpolationError::Undefined(variable) => AppError::ConfigVariable {
+                filename,
+                name: name.to_string(),
+                variable,
+            },
+            InterpolationError::Invalid(reason) => AppError::ConfigEntry {
+                filename,
+                name: name.to_string(),
+                reason,
+            },
+        })
+}
+
+/// Values of `other` replace the values of `table`, except tables that are merged.
+pub fn override_table(table: &mut toml::Table, other: &toml::Table) {
+    for (key, value) in other {
+        match (table.get_mut(key), value) {
+            (Some(toml::Value::Table(table)), toml::Value::Table(other)) => {
+                override_table(table, other)
+            }
+            _ => {
+                table.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+/// Walks nested tables of configuration file. Tables that have `hostname` (or `extends`) are
+/// servers and other tables are groups of servers, so `[prod.eu.web1]` is server `web1` in group
+/// `prod.eu`.
+///
+/// A server can also set its group via `group` field, e.g. `[web1]` with `group = "prod.eu"`.
+///
+/// An invalid entry doesn't stop the walk: its errors are added to `error_list` and the other
+/// entries are still collected.
+fn collect_servers(
+    source: &ServerSource,
+    table: &toml::Table,
+    group_path: Vec<String>,
+    servers: &mut HashMap<String, ConfigServer>,
+    error_list: &mut Vec<AppError>,
+) {
+    for (key, value) in table.iter() {
+        let mut path = group_path.clone();
+        path.push(key.clone());
+        let name = path.join(".");
+        let Some(entry_table) = value.as_table() else {
+            error_list.push(AppError::ConfigEntry {
+                filename: source.origin(&name),
+                name: name.clone(),
+                reason: "expected a server or a group table".to_string(),
+            });
+            continue;
+        };
+        if !entry_table.contains_key("hostname") && !entry_table.contains_key("extends") {
+            // Fields make it a server (e.g. with a misspelled `hostname`), not a group:
+            let field_list: Vec<_> = entry_table
+                .iter()
+                .filter(|(_, value)| !value.is_table())
+                .map(|(key, _)| format!("`{}`", key))
+                .collect();
+            if !field_list.is_empty() {
+                error_list.push(AppError::ConfigEntry {
+                    filename: source.origin(&name),
+                    name: name.clone(),
+                    reason: format!(
+                        "missing field `hostname` (found {} but a group can only contain servers and groups)",
+                        field_list.join(", ")
+                    ),
+                });
+                continue;
+            }
+            collect_servers(source, entry_table, path, servers, error_list);
+            continue;
+        }
+        if let Err(error) = try_collect_server(
+            source,
+            key,
+            entry_table,
+            path,
+            group_path.clone(),
+            servers,
+            error_list,
+        ) {
+            error_list.push(error);
+        }
+    }
+}
+
+/// Adds servers of table `key` (more than one if its hostname has ranges or lists). Errors of
+/// single expanded servers are added to `error_list` and the other servers are still added.
+fn try_collect_server(
+    source: &ServerSource,
+    key: &str,
+    entry_table: &toml::Table,
+    path: Vec<String>,
+    mut group_path: Vec<String>,
+    servers: &mut HashMap<String, ConfigServer>,
+    error_list: &mut Vec<AppError>,
+) -> Result<(), AppError> {
+    let name = path.join(".");
+    // `ssh_options` is the only table that a server can have:
+    if let Some((child_key, _)) = entry_table
+        .iter()
+        .find(|(key, value)| value.is_table() && key.as_str() != "ssh_options")
+    {
+        return Err(AppError::ConfigEntry {
+            filename: source.origin(&format!("{}.{}", name, child_key)),
+            name: format!("{}.{}", name, child_key),
+            reason: format!("server {:?} can not contain other servers or groups", name),
+        });
+    }
+    let mut entry_table = source.try_inherit(entry_table, &mut [name.clone()].to_vec())?;
+    if source.sssh.interpolate {
+        try_interpolate_table(&mut entry_table, &name, source.origin(&name))?;
+    }
+    let entry_error = |error: toml::de::Error| AppError::ConfigEntry {
+        filename: source.origin(&name),
+        name: name.clone(),
+        reason: error.to_string().trim().replace('\n', " "),
+    };
+    let filename = source.origin(&format!("{}.hostname", name));
+    let pattern_error = |reason: String| AppError::ConfigEntry {
+        filename: filename.clone(),
+        name: name.clone(),
+        reason,
+    };
+    // Fields of server itself, to find its name, hostname and tags that rules match:
+    let mut server: ConfigServer =
+        toml::Value::Table(entry_table.clone())
+            .try_into()
+            .map_err(|error| {
+                // Ranges and lists of hostname can still be checked, e.g. if only `port` is invalid:
+                if let Some(hostname) = entry_table.get("hostname").and_then(|value| value.as_str())
+                {
+                    let server = ConfigServer {
+                        hostname: hostname.to_string(),
+                        ..Default::default()
+                    };
+                    if let Err(reason) = expand_server(key, server) {
+                        error_list.push(pattern_error(reason));
+                    }
+                }
+                entry_error(error)
+            })?;
+    server
+        .group
+        .split('.')
+        .filter(|group| !group.trim().is_empty())
+        .for_each(|group| group_path.push(group.trim().to_string()));
+    server.group = group_path.join(".");
+    server.filename = filename.clone();
+    server.table_path = path;
+    for (key, mut server) in expand_server(key, server).map_err(pattern_error)? {
+        let mut group_path = group_path.clone();
+        group_path.push(key);
+        server.name = group_path.join(".");
+        let mut server_table = source.sssh.server_defaults(&server);
+        override_table(&mut server_table, &entry_table);
+        server_table.insert(
+            "hostname".to_string(),
+            toml::Value::from(server.hostname.clone()),
+        );
+        server = match toml::Value::Table(server_table).try_into() {
+            Ok(defaults_server) => ConfigServer {
+                name: server.name,
+                filename: server.filename,
+                table_path: server.table_path,
+                group: server.group,
+                ..defaults_server
+            },
+            Err(error) => {
+                error_list.push(entry_error(error));
+                continue;
+            }
+        };
+        if let Some(other_server) = servers.get(&server.name) {
+            error_list.push(if other_server.filename != filename {
+                AppError::ConfigDuplicateServer {
+                    name: server.name,
+                    filename: other_server.filename.clone(),
+                    other_filename: filename.clone(),
+                }
+            } else {
+                AppError::ConfigEntry {
+                    filename: filename.clone(),
+                    name: server.name,
+                    reason: "server is defined more than once".to_string(),
+                }
+            });
+            continue;
+        }
+        servers.insert(server.name.clone(), server);
+    }
+    Ok(())
+}
+
+/// Expands ranges and lists of `hostname` (see [`expand::expand`]) into one server for each
+/// hostname and returns them with their names.
+///
+/// If the table key is also a pattern, e.g. `["web[01-40]"]`, it's expanded the same way and
+/// its names are paired with the hostnames in order. Otherwise the values that replaced each
+/// range or list are appended to the key, so `[web]` with `hostname = "web[01-02].example.com"`
+/// becomes `web-01` and `web-02`.
+fn expand_server(key: &str, server: ConfigServer) -> Result<Vec<(String, ConfigServer)>, String> {
+    if !expand::is_pattern(&server.hostname) {
+        if expand::is_pattern(key) {
+            return Err(format!(
+                "name {:?} is a pattern, but hostname {:?} is not",
+                key, server.hostname
+            ));
+        }
+        return Ok(vec![(key.to_string(), server)]);
+    }
+    let hostname_list = expand::expand(&server.hostname)?;
+    let name_list = if expand::is_pattern(key) {
+        let name_list = expand::expand(key)?;
+        if name_list.len() != hostname_list.len() {
+            return Err(format!(
+                "name {:?} expands to {} names, but hostname {:?} expands to {} hostnames",
+                key,
+                name_list.len(),
+                server.hostname,
+                hostname_list.len()
+            ));
+        }
+        name_list
+            .into_iter()
+            .map(|expansion| expansion.value)
+            .collect()
+    } else {
+        hostname_list
+            .iter()
+            .map(|expansion| format!("{}-{}", key, expansion.part_list.join("-")))
+            .collect::<Vec<_>>()
+    };
+    Ok(name_list
+        .into_iter()
+        .zip(hostname_list)
+        .map(|(name, expansion)| {
+            let mut server = server.clone();
+            server.hostname = expansion.value;
+            (name, server)
+        })
+        .collect())
+}
+
+/// Searches current directory and its ancestors for a project-local configuration file. Home
+/// directory (where the user configuration is) and everything above it are not searched.
+pub fn find_project_configuration_file() -> Option<PathBuf> {
+    let home_directory = home_dir();
+    let mut maybe_directory = std::env::current_dir().ok();
+    while let Some(directory) = maybe_directory {
+        if Some(&directory) == home_directory.as_ref() {
+            break;
+        }
+        let filename = directory.join(PROJECT_CONFIGURATION_FILENAME);
+        if filename.is_file() {
+            debug!(filename = ?filename, "Found project configuration file");
+            return Some(filename);
+        }
+        maybe_directory = directory.parent().map(PathBuf::from);
+    }
+    None
+}
+
+/// Configuration file that a value (or any value inside a table) comes from.
+pub fn origin(origin_map: &BTreeMap<String, PathBuf>, path: &str, filename: &Path) -> PathBuf {
+    let prefix = format!("{}.", path);
+    origin_map
+        .get(path)
+        .or_else(|| {
+            origin_map
+                .iter()
+                .find(|(other_path, _)| other_path.starts_with(&prefix))
+                .map(|(_, filename)| filename)
+        })
+        .cloned()
+        .unwrap_or_else(|| filename.to_path_buf())
+}
+
+/// Merges `other` into `table` and records which file each merged value comes from.
+fn merge_table(
+    table: &mut toml::Table,
+    other: toml::Table,
+    filename: &PathBuf,
+    path: String,
+    origin_map: &mut BTreeMap<String, PathBuf>,
+) {
+    for (key, value) in other {
+        let key_path = if path.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", path, key)
+        };
+        match (table.get_mut(&key), value) {
+            (Some(toml::Value::Table(table)), toml::Value::Table(other)) => {
+                merge_table(table, other, filename, key_path, origin_map)
+            }
+            (_, value) => {
+                let prefix = format!("{}.", key_path);
+                origin_map.retain(|path, _| *path != key_path && !path.starts_with(&prefix));
+                record_origin(&value, key_path, filename, origin_map);
+                table.insert(key, value);
+            }
+        }
+    }
+}
+
+fn record_origin(
+    value: &toml::Value,
+    path: String,
+    filename: &PathBuf,
+    origin_map: &mut BTreeMap<String, PathBuf>,
+) {
+    match value {
+        toml::Value::Table(table) if !table.is_empty() => table.iter().for_each(|(key, value)| {
+            record_origin(value, format!("{}.{}", path, key), filename, origin_map)
+        }),
+        _ => {
+            origin_map.insert(path, filename.clone());
+        }
+    }
+}
+
+/// Relative `include` patterns of a layer are relative to its own directory, so they must be
+/// resolved before layers are merged.
+fn make_include_absolute(filename: &Path, table: &mut toml::Table) {
+    let directory = std::env::current_dir()
+        .unwrap_or_default()
+        .join(filename.parent().unwrap_or(Path::new("")));
+    if let Some(toml::Value::Array(pattern_list)) = table
+        .get_mut("sssh")
+        .and_then(|sssh| sssh.as_table_mut())
+        .and_then(|sssh| sssh.get_mut("include"))
+    {
+        pattern_list.iter_mut().for_each(|pattern| {
+            if let toml::Value::String(pattern) = pattern {
+                if !pattern.starts_with("~/") {
+                    *pattern = directory
+                        .join(pattern.as_str())
+                        .to_string_lossy()
+                        .to_string()
+                }
+            }
+        })
+    }
+}
+
+/// Expands `include` patterns of configuration file to a sorted list of files.
+///
+/// Relative patterns are relative to directory of configuration file and `~/` is the home
+/// directory. Patterns without wildcards are used as they are, so a missing file is an error but
+/// a pattern that matches no file is not.
+fn include_filename_list(
+    filename: &Path,
+    pattern_list: &[String],
+) -> Result<Vec<PathBuf>, AppError> {
+    let directory = filename.parent().map(PathBuf::from).unwrap_or_default();
+    let mut include_filename_list = Vec::new();
+    for pattern in pattern_list {
+        let path = match pattern.strip_prefix("~/") {
+            Some(rest) => home_dir().unwrap_or_default().join(rest),
+            None => directory.join(pattern),
+        };
+        if !pattern.contains(['*', '?', '[']) {
+            include_filename_list.push(path);
+            continue;
+        }
+        let mut path_list = glob::glob(path.to_str().unwrap_or_default())
+            .map_err(|error| AppError::ConfigInclude {
+                filename: filename.to_path_buf(),
+                pattern: pattern.clone(),
+                reason: error.to_string(),
+            })?
+            .filter_map(|path| match path {
+                Ok(path) => Some(path),
+                Err(error) => {
+                    warn!(error = ?error, pattern = pattern, "Could not read included path");
+                    None
+                }
+            })
+            .filter(|path| path.is_file())
+            .collect::<Vec<_>>();
+        if path_list.is_empty() {
+            debug!(pattern = pattern, "Include pattern matches no file");
+        }
+        path_list.sort();
+        include_filename_list.append(&mut path_list);
+    }
+    Ok(include_filename_list)
+}
+
+fn try_load_theme_file(filename: PathBuf, config_theme: &ConfigTheme) -> Result<Theme, AppError> {
+    let theme = fs::read_to_string(filename.clone()).map_err(|error| AppError::FileRead {
+        title: "theme",
+        filename: filename.clone(),
+        source: error,
+    })?;
+    let file_config_theme: ConfigTheme =
+        toml::from_str(&theme).map_err(|error| AppError::ConfigSyntax {
+            filename: filename.clone(),
+            source: error,
+        })?;
+    Theme::try_from(&config_theme.merge(&file_config_theme))
+        .map_err(|reason| AppError::ConfigTheme { filename, reason })
 }
 
 fn default_configuration_filename() -> &'static str {
@@ -393,10 +1744,68 @@ fn default_script_filename() -> &'static str {
     )
 }
 
+fn default_history_filename() -> &'static str {
+    Box::leak(
+        try_join_to_user_data_directory(DEFAULT_HISTORY_FILENAME)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string()
+            .into_boxed_str(),
+    )
+}
+
+fn default_inventory_cache_filename() -> &'static str {
+    Box::leak(
+        try_join_to_user_cache_directory(DEFAULT_INVENTORY_CACHE_FILENAME)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string()
+            .into_boxed_str(),
+    )
+}
+
+fn default_trust_directory() -> &'static str {
+    Box::leak(
+        try_join_to_user_data_directory(DEFAULT_TRUST_DIRECTORY_NAME)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string()
+            .into_boxed_str(),
+    )
+}
+
 fn default_port_number() -> u16 {
     DEFAULT_PORT_NUMBER
 }
 
+/// OpenSSH options are strings, but numbers and booleans (as `yes`/`no`) are accepted too.
+fn deserialize_ssh_options<'de, D>(deserializer: D) -> Result<BTreeMap<String, String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    BTreeMap::<String, toml::Value>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(key, value)| {
+            let value = match value {
+                toml::Value::String(value) => value,
+                toml::Value::Integer(value) => value.to_string(),
+                toml::Value::Float(value) => value.to_string(),
+                toml::Value::Boolean(value) => if value { "yes" } else { "no" }.to_string(),
+                value => {
+                    return Err(serde::de::Error::custom(format!(
+                    "invalid value {} of SSH option {:?}, expected a string, a number or a boolean",
+                    value, key
+                )))
+                }
+            };
+            Ok((key, value))
+        })
+        .collect()
+}
+
 fn default_editor_command() -> &'static str {
     for (command, _) in TO_BE_SEARCHED_EDITOR_LIST.iter().cloned() {
         if pathsearch::find_executable_in_path(command).is_some() {
@@ -422,3 +1831,17 @@ fn try_join_to_user_configuration_directory(filename: &'static str) -> Result<Pa
         .context("Could not get user's configuration directory")?
         .join(filename))
 }
+
+fn try_join_to_user_cache_directory(filename: &'static str) -> Result<PathBuf> {
+    // e.g. Linux: ~/.cache/<filename>
+    Ok(cache_dir()
+        .context("Could not get user's cache directory")?
+        .join(filename))
+}
+
+fn try_join_to_user_data_directory(filename: &'static str) -> Result<PathBuf> {
+    // e.g. Linux: ~/.local/share/<filename>
+    Ok(data_dir()
+        .context("Could not get user's data directory")?
+        .join(filename))
+}
@@ -0,0 +1,371 @@
+use crate::error::AppError;
+use crate::settings::{ConfigServer, DEFAULT_PORT_NUMBER};
+use dirs::home_dir;
+use std::{fs, path::PathBuf};
+use tracing::{debug, warn};
+
+const DEFAULT_SSH_CONFIG_FILENAME: &str = ".ssh/config";
+pub const MANAGED_SECTION_BEGIN: &str =
+    "# BEGIN sssh managed section (generated by `sssh export ssh-config --write`, do not edit)";
+pub const MANAGED_SECTION_END: &str = "# END sssh managed section";
+
+pub fn default_ssh_config_filename() -> &'static str {
+    // e.g. Linux: ~/.ssh/config
+    Box::leak(
+        home_dir()
+            .unwrap_or_default()
+            .join(DEFAULT_SSH_CONFIG_FILENAME)
+            .to_str()
+            .unwrap()
+            .to_string()
+            .into_boxed_str(),
+    )
+}
+
+/// Reads `Host` blocks of an OpenSSH client configuration file.
+///
+/// Only `HostName`, `User`, `Port`, `IdentityFile`, `ProxyJump` and `ForwardAgent` are used. Host
+/// patterns that contain wildcards (`*` and `?`) or are negated (`!`) do not point to a single
+/// server, so they are skipped. The section written by `sssh export ssh-config --write` is skipped
+/// too.
+pub fn try_read(filename: PathBuf) -> Result<Vec<ConfigServer>, AppError> {
+    let ssh_config = fs::read_to_string(filename.clone()).map_err(|error| AppError::FileRead {
+        title: "SSH configuration",
+        filename: filename.clone(),
+        source: error,
+    })?;
+    parse(filename, ssh_config)
+}
+
+fn parse(filename: PathBuf, ssh_config: String) -> Result<Vec<ConfigServer>, AppError> {
+    let mut server_list = Vec::new();
+    // Servers of the `Host` block we are currently in:
+    let mut block_server_list: Vec<ConfigServer> = Vec::new();
+    let mut in_managed_section = false;
+    for (line_offset, line) in ssh_config.lines().enumerate() {
+        let line_number = line_offset + 1;
+        let line = line.trim();
+        // Do not import what we have exported ourselves:
+        if line == MANAGED_SECTION_BEGIN {
+            flush(&mut server_list, &mut block_server_list);
+            in_managed_section = true;
+        } else if line == MANAGED_SECTION_END {
+            in_managed_section = false;
+            continue;
+        }
+        if in_managed_section || line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        // Both `Keyword value` and `Keyword=value` are valid:
+        let (keyword, value) =
+            match line.find(|character: char| character.is_whitespace() || character == '=') {
+                Some(offset) => (
+                    &line[..offset],
+                    line[offset..]
+                        .trim_start()
+                        .trim_start_matches('=')
+                        .trim()
+                        .trim_matches('"'),
+                ),
+                None => (line, ""),
+            };
+        match keyword.to_lowercase().as_str() {
+            "host" => {
+                flush(&mut server_list, &mut block_server_list);
+                for pattern in value.split_whitespace() {
+                    if pattern.contains(['*', '?']) || pattern.starts_with('!') {
+                        debug!(
+                            pattern = pattern,
+                            line = line_number,
+                            "Skip wildcard host pattern"
+                        );
+                        continue;
+                    }
+                    block_server_list.push(ConfigServer {
+                        name: pattern.to_string(),
+                        ..Default::default()
+                    })
+                }
+            }
+            "match" => {
+                // Options after `Match` are conditional, so they belong to no server:
+                flush(&mut server_list, &mut block_server_list);
+                debug!(line = line_number, "Skip `Match` block");
+            }
+            // Like `ssh` the first obtained value of each option is used:
+            "hostname" => block_server_list.iter_mut().for_each(|server| {
+                if server.hostname.is_empty() {
+                    server.hostname = value.to_string()
+                }
+            }),
+            "user" => block_server_list.iter_mut().for_each(|server| {
+                if server.username_list.is_empty() {
+                    server.username_list.push(value.to_string())
+                }
+            }),
+            "identityfile" => block_server_list.iter_mut().for_each(|server| {
+                if server.identity_file.is_none() {
+                    server.identity_file = Some(value.to_string())
+                }
+            }),
+            "proxyjump" => block_server_list.iter_mut().for_each(|server| {
+                if server.proxy_jump.is_none() {
+                    server.proxy_jump = Some(value.to_string())
+                }
+            }),
+            "forwardagent" => {
+                let forward_agent = match value.to_lowercase().as_str() {
+                    "yes" => true,
+                    "no" => false,
+                    // A socket path or an environment variable:
+                    _ => {
+                        debug!(
+                            line = line_number,
+                            "Skip `ForwardAgent` that is not `yes` or `no`"
+                        );
+                        continue;
+                    }
+                };
+                block_server_list.iter_mut().for_each(|server| {
+                    if server.forward_agent.is_none() {
+                        server.forward_agent = Some(forward_agent)
+                    }
+                })
+            }
+            "port" => {
+                let port = value
+                    .parse::<u16>()
+                    .map_err(|_| AppError::SshConfigSyntax {
+                        filename: filename.clone(),
+                        line: line_number,
+                        reason: format!("invalid port number {:?}", value),
+                    })?;
+                block_server_list.iter_mut().for_each(|server| {
+                    if server.port == 0 {
+                        server.port = port
+                    }
+                })
+            }
+            "include" => {
+                warn!(
+                    line = line_number,
+                    filename = ?filename,
+                    "`Include` directive in SSH configuration is not supported, skipped it"
+                )
+            }
+            _ => (),
+        }
+    }
+    flush(&mut server_list, &mut block_server_list);
+    Ok(server_list)
+}
+
+fn flush(server_list: &mut Vec<ConfigServer>, block_server_list: &mut Vec<ConfigServer>) {
+    block_server_list.iter_mut().for_each(|server| {
+        // Without `HostName`, `ssh` connects to the host alias itself:
+        if server.hostname.is_empty() {
+            server.hostname = server.name.clone()
+        }
+        if server.port == 0 {
+            server.port = DEFAULT_PORT_NUMBER
+        }
+    });
+    server_list.append(block_server_list);
+}
+
+/// Renders servers as `Host` blocks of an OpenSSH client configuration file and returns them with
+/// the number of rendered servers. Servers whose names can not be host aliases are skipped.
+pub fn render(server_list: &[ConfigServer]) -> (String, usize) {
+    let mut server_list = server_list.to_vec();
+    server_list.sort_by_key(|server| server.name.clone());
+    let block_list: Vec<_> = server_list
+        .iter()
+        .filter(|server| {
+            if server.name.contains(char::is_whitespace) {
+                warn!(
+                    server_name = server.name,
+                    "Server name contains whitespace and can not be a host alias, skipped it"
+                );
+                return false;
+            }
+            true
+        })
+        .map(|server| {
+            let mut block = format!("Host {}\n    HostName {}\n", server.name, server.hostname);
+            if server.port != DEFAULT_PORT_NUMBER {
+                block += format!("    Port {}\n", server.port).as_str();
+            }
+            if let Some(username) = server.username_list.first() {
+                block += format!("    User {}\n", username).as_str();
+            }
+            if let Some(identity_file) = &server.identity_file {
+                block += format!("    IdentityFile {}\n", identity_file).as_str();
+            }
+            if let Some(proxy_jump) = &server.proxy_jump {
+                block += format!("    ProxyJump {}\n", proxy_jump).as_str();
+            }
+            if let Some(forward_agent) = server.forward_agent {
+                block += format!(
+                    "    ForwardAgent {}\n",
+                    if forward_agent { "yes" } else { "no" }
+                )
+                .as_str();
+            }
+            for (key, value) in server.ssh_options.iter() {
+                block += format!("    {} {}\n", key, value).as_str();
+            }
+            block
+        })
+        .collect();
+    (block_list.join("\n"), block_list.len())
+}
+
+/// Replaces the section between [`MANAGED_SECTION_BEGIN`] and [`MANAGED_SECTION_END`] markers
+/// with `section` and keeps everything else untouched.
+///
+/// If there is no managed section yet, it's appended to the end of file, so user's own entries
+/// still take precedence.
+pub fn replace_managed_section(ssh_config: &str, section: &str) -> String {
+    let managed_section = format!(
+        "{}\n{}{}{}\n",
+        MANAGED_SECTION_BEGIN,
+        section,
+        if section.is_empty() || section.ends_with('\n') {
+            ""
+        } else {
+            "\n"
+        },
+        MANAGED_SECTION_END
+    );
+    match (
+        ssh_config.find(MANAGED_SECTION_BEGIN),
+        ssh_config.find(MANAGED_SECTION_END),
+    ) {
+        (Some(begin), Some(end)) if begin < end => {
+            let mut after = &ssh_config[end + MANAGED_SECTION_END.len()..];
+            if after.starts_with('\n') {
+                after = &after[1..];
+            }
+            format!("{}{}{}", &ssh_config[..begin], managed_section, after)
+        }
+        _ => {
+            let separator = if ssh_config.is_empty() || ssh_config.ends_with("\n\n") {
+                ""
+            } else if ssh_config.ends_with('\n') {
+                "\n"
+            } else {
+                "\n\n"
+            };
+            format!("{}{}{}", ssh_config, separator, managed_section)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(ssh_config: &str) -> Vec<ConfigServer> {
+        parse(PathBuf::from("config"), ssh_config.to_string()).unwrap()
+    }
+
+    #[test]
+    fn parse_host_blocks() {
+        let server_list = parse_str(
+            "# comment\n\
+             Host web1 web2\n    HostName web.example.com\n    User deploy\n    Port 2222\n\n\
+             Host db\n    IdentityFile ~/.ssh/db\n    ProxyJump bastion\n    ForwardAgent yes\n",
+        );
+        assert_eq!(server_list.len(), 3);
+        assert_eq!(server_list[0].name, "web1");
+        assert_eq!(server_list[1].name, "web2");
+        assert_eq!(server_list[1].hostname, "web.example.com");
+        assert_eq!(server_list[1].username_list, ["deploy"]);
+        assert_eq!(server_list[1].port, 2222);
+        // Without `HostName` the alias is the hostname:
+        assert_eq!(server_list[2].hostname, "db");
+        assert_eq!(server_list[2].port, DEFAULT_PORT_NUMBER);
+        assert_eq!(server_list[2].identity_file.as_deref(), Some("~/.ssh/db"));
+        assert_eq!(server_list[2].proxy_jump.as_deref(), Some("bastion"));
+        assert_eq!(server_list[2].forward_agent, Some(true));
+    }
+
+    #[test]
+    fn parse_key_value_lines() {
+        let server_list =
+            parse_str("Host=web\nHostName=web.example.com\nUser = \"deploy\"\nport=22\n");
+        assert_eq!(server_list.len(), 1);
+        assert_eq!(server_list[0].hostname, "web.example.com");
+        assert_eq!(server_list[0].username_list, ["deploy"]);
+    }
+
+    #[test]
+    fn parse_first_value_wins() {
+        let server_list = parse_str("Host web\nHostName a\nHostName b\nUser x\nUser y\n");
+        assert_eq!(server_list[0].hostname, "a");
+        assert_eq!(server_list[0].username_list, ["x"]);
+    }
+
+    #[test]
+    fn parse_skips_wildcards_and_match_blocks() {
+        let server_list = parse_str(
+            "Host * !bad web?\n    User everyone\n\
+             Host web\n    HostName web.example.com\n\
+             Match host web\n    User matched\n    HostName other\n",
+        );
+        assert_eq!(server_list.len(), 1);
+        assert_eq!(server_list[0].name, "web");
+        assert_eq!(server_list[0].hostname, "web.example.com");
+        assert!(server_list[0].username_list.is_empty());
+    }
+
+    #[test]
+    fn parse_skips_managed_section() {
+        let ssh_config = replace_managed_section("Host web\n", "Host exported\n    HostName x\n");
+        let server_list = parse_str(&ssh_config);
+        assert_eq!(server_list.len(), 1);
+        assert_eq!(server_list[0].name, "web");
+    }
+
+    #[test]
+    fn parse_invalid_port() {
+        let error = parse(PathBuf::from("config"), "Host web\nPort ssh\n".to_string()).unwrap_err();
+        assert!(matches!(error, AppError::SshConfigSyntax { line: 2, .. }));
+    }
+
+    #[test]
+    fn replace_appends_section() {
+        assert_eq!(
+            replace_managed_section("Host web\n", "Host a\n"),
+            format!(
+                "Host web\n\n{}\nHost a\n{}\n",
+                MANAGED_SECTION_BEGIN, MANAGED_SECTION_END
+            )
+        );
+        assert_eq!(
+            replace_managed_section("", "Host a"),
+            format!(
+                "{}\nHost a\n{}\n",
+                MANAGED_SECTION_BEGIN, MANAGED_SECTION_END
+            )
+        );
+    }
+
+    #[test]
+    fn replace_existing_section() {
+        let ssh_config = format!(
+            "Host before\n\n{}\nHost old\n{}\nHost after\n",
+            MANAGED_SECTION_BEGIN, MANAGED_SECTION_END
+        );
+        assert_eq!(
+            replace_managed_section(&ssh_config, "Host new\n"),
+            format!(
+                "Host before\n\n{}\nHost new\n{}\nHost after\n",
+                MANAGED_SECTION_BEGIN, MANAGED_SECTION_END
+            )
+        );
+        // Replacing it again changes nothing:
+        let replaced = replace_managed_section(&ssh_config, "Host new\n");
+        assert_eq!(replace_managed_section(&replaced, "Host new\n"), replaced);
+    }
+}
@@ -0,0 +1,58 @@
+use crate::error::AppError;
+use crate::settings::{Settings, SubCommand};
+use crate::validation::{self, Severity};
+use anyhow::Result;
+use clap::ValueEnum;
+use std::io::{self, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, ValueEnum)]
+pub enum CheckFormat {
+    /// `file:line:column: severity: message` lines, like compilers print.
+    #[default]
+    Text,
+    /// An array of objects, for editors and other tools.
+    Json,
+}
+
+pub fn run(settings: &mut Settings) -> Result<(), AppError> {
+    let format = if let SubCommand::Check { format } = settings.subcommand {
+        format
+    } else {
+        // It's already checked in main.rs
+        unreachable!()
+    };
+    let layer_list = settings.try_read_configuration()?;
+    let file_count = layer_list.len();
+    let (maybe_config, diagnostic_list) =
+        validation::validate(layer_list, &settings.configuration_file);
+    let output = match format {
+        CheckFormat::Text if diagnostic_list.is_empty() => format!(
+            "No problems found in {} configuration file(s).\n",
+            file_count
+                + maybe_config
+                    .map(|config| config.include_list.len())
+                    .unwrap_or_default()
+        ),
+        CheckFormat::Text => diagnostic_list
+            .iter()
+            .map(|diagnostic| format!("{}\n", diagnostic))
+            .collect(),
+        CheckFormat::Json => {
+            serde_json::to_string_pretty(&diagnostic_list).unwrap_or_default() + "\n"
+        }
+    };
+    match io::stdout().write_all(output.as_bytes()) {
+        Err(error) if error.kind() != io::ErrorKind::BrokenPipe => {
+            return Err(AppError::StdoutWrite { source: error })
+        }
+        _ => (),
+    }
+    let error_count = diagnostic_list
+        .iter()
+        .filter(|diagnostic| diagnostic.severity == Severity::Error)
+        .count();
+    if error_count > 0 {
+        return Err(AppError::ConfigCheck { error_count });
+    }
+    Ok(())
+}
@@ -1,13 +1,521 @@
+use crate::backup::{self, Backup};
 use crate::error::AppError;
-use crate::settings::Settings;
+use crate::settings::{self, ConfigMatch, Settings, SubCommand};
 use anyhow::Result;
-use clap::crate_name;
+use clap::{crate_name, Parser};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml_edit::{Document, Item, Table};
+
+/// Lines of unchanged context around each change in diffs.
+const DIFF_CONTEXT_LINE_COUNT: usize = 3;
+
+/// Backups of configuration file that sssh takes before it changes the file.
+#[derive(Debug, Clone, PartialEq, Parser)]
+pub enum Backups {
+    /// List backups of configuration file (newest first) with their number.
+    History,
+    /// Print changes of configuration file since a backup.
+    Diff {
+        /// Number of the backup in `config history`.
+        number: usize,
+    },
+    /// Restore configuration file from a backup. The current file is backed up first.
+    Rollback {
+        /// Number of the backup in `config history`.
+        number: usize,
+    },
+}
 
 pub fn run(settings: &mut Settings) -> Result<(), AppError> {
+    let (effective, maybe_backup) = if let SubCommand::Config {
+        effective,
+        ref backup,
+    } = settings.subcommand
+    {
+        (effective, backup.clone())
+    } else {
+        // It's already checked in main.rs
+        unreachable!()
+    };
+    // Backups are needed the most when configuration is broken, so it's not loaded for them:
+    match maybe_backup {
+        Some(Backups::History) => return print_history(settings),
+        Some(Backups::Diff { number }) => return print_diff(settings, number),
+        Some(Backups::Rollback { number }) => return rollback(settings, number),
+        None => (),
+    }
     settings.try_load_and_set_configuration()?;
+    if effective {
+        return print_effective(settings);
+    }
     println!("# file: {:?}", settings.configuration_file.clone());
     println!("# Use `{} edit` to edit this file.", crate_name!());
+    let other_layer_list = settings
+        .configuration
+        .layer_list
+        .iter()
+        .filter(|filename| **filename != settings.configuration_file)
+        .map(|filename| format!("{:?}", filename))
+        .collect::<Vec<_>>();
+    if !other_layer_list.is_empty() {
+        println!("# It's merged with {}.", other_layer_list.join(" and "));
+        println!(
+            "# Use `{} config --effective` to see the merged configuration.",
+            crate_name!()
+        );
+    }
     println!();
     print!("{}", settings.configuration.raw);
+    for (filename, include) in settings.configuration.include_list.iter() {
+        println!();
+        println!("# included file: {:?}", filename);
+        println!();
+        print!("{}", include);
+    }
+    Ok(())
+}
+
+fn print_effective(settings: &mut Settings) -> Result<(), AppError> {
+    let config = &settings.configuration;
+    println!("# Merged configuration of layers (lowest precedence first):");
+    config
+        .layer_list
+        .iter()
+        .enumerate()
+        .for_each(|(index, filename)| println!("#   {}. {:?}", index + 1, filename));
+    println!("# Tables are merged key by key and other values replace the ones from lower layers.");
+    println!("# Each value is followed by the file it comes from.");
+    if !config.sssh.match_list.is_empty() {
+        println!("# Match rules ([[sssh.match]]) are checked in order for each server and a rule matches");
+        println!("# if all of its conditions match. For each field, the first matching rule that sets it");
+        println!("# wins. Fields of server itself (and its templates) take precedence over rules, and rules");
+        println!("# take precedence over [sssh.defaults]:");
+        for (index, rule) in config.sssh.match_list.iter().enumerate() {
+            let mut name_list = config
+                .servers
+                .values()
+                .filter(|server| rule.matches(server))
+                .map(|server| server.name.clone())
+                .collect::<Vec<_>>();
+            name_list.sort();
+            println!(
+                "#   {}. {} matches {}",
+                index + 1,
+                describe_rule(rule),
+                if name_list.is_empty() {
+                    "no servers".to_string()
+                } else {
+                    name_list.join(", ")
+                }
+            );
+        }
+    }
+    if !config.include_list.is_empty() {
+        println!("# Servers of included files are not merged:");
+        config
+            .include_list
+            .iter()
+            .for_each(|(filename, _)| println!("#   {:?}", filename));
+    }
+    println!();
+    let mut document = toml::to_string(&config.effective)
+        .unwrap_or_default()
+        .parse::<Document>()
+        .map_err(|source| AppError::ConfigEdit {
+            filename: settings.configuration_file.clone(),
+            source,
+        })?;
+    annotate_origin(
+        document.as_table_mut(),
+        String::new(),
+        &config.origin_map,
+        &settings.configuration_file,
+    );
+    print!("{}", document);
+    Ok(())
+}
+
+fn print_history(settings: &mut Settings) -> Result<(), AppError> {
+    let backup_list = backup::try_list(&settings.configuration_file)?;
+    if backup_list.is_empty() {
+        println!(
+            "There is no backup of configuration file {:?} yet.",
+            settings.configuration_file
+        );
+        return Ok(());
+    }
+    let configuration = read_configuration(settings)?;
+    println!(
+        "Backups of configuration file {:?} (newest first):",
+        settings.configuration_file
+    );
+    for (index, backup) in backup_list.iter().enumerate() {
+        let backup_configuration = backup.try_read()?;
+        let diff = diff_line_list(&backup_configuration, &configuration);
+        let count = |kind: char| diff.iter().filter(|(other, _)| *other == kind).count();
+        println!(
+            "{:>4}. {} UTC  (+{} -{} lines since then)",
+            index + 1,
+            backup.time(),
+            count('+'),
+            count('-')
+        );
+    }
+    println!(
+        "Use `{} config diff <NUMBER>` to see the changes.",
+        crate_name!()
+    );
     Ok(())
 }
+
+fn print_diff(settings: &mut Settings, number: usize) -> Result<(), AppError> {
+    let backup = try_find_backup(settings, number)?;
+    let diff = unified_diff(
+        &backup.try_read()?,
+        &read_configuration(settings)?,
+        &format!("backup {} ({} UTC)", number, backup.time()),
+        &settings.configuration_file.display().to_string(),
+    );
+    if diff.is_empty() {
+        println!(
+            "Configuration file is the same as backup {} ({} UTC).",
+            number,
+            backup.time()
+        );
+    } else {
+        print!("{}", diff);
+    }
+    Ok(())
+}
+
+fn rollback(settings: &mut Settings, number: usize) -> Result<(), AppError> {
+    let backup = try_find_backup(settings, number)?;
+    settings.try_write_configuration(backup.try_read()?)?;
+    println!(
+        "Restored configuration file {:?} from backup {} ({} UTC).",
+        settings.configuration_file,
+        number,
+        backup.time()
+    );
+    Ok(())
+}
+
+fn try_find_backup(settings: &Settings, number: usize) -> Result<Backup, AppError> {
+    let backup_list = backup::try_list(&settings.configuration_file)?;
+    number
+        .checked_sub(1)
+        .and_then(|index| backup_list.get(index))
+        .cloned()
+        .ok_or_else(|| AppError::BackupNotFound {
+            filename: settings.configuration_file.clone(),
+            number,
+            count: backup_list.len(),
+        })
+}
+
+fn read_configuration(settings: &Settings) -> Result<String, AppError> {
+    fs::read_to_string(settings.configuration_file.clone()).map_err(|error| AppError::FileRead {
+        title: "configuration",
+        filename: settings.configuration_file.clone(),
+        source: error,
+    })
+}
+
+/// Changes of `new` compared to `old` line by line: `' '` for unchanged lines, `'-'` for removed
+/// and `'+'` for added ones.
+fn diff_line_list<'a>(old: &'a str, new: &'a str) -> Vec<(char, &'a str)> {
+    let old_line_list: Vec<_> = old.lines().collect();
+    let new_line_list: Vec<_> = new.lines().collect();
+    // Myers' diff in linear space: furthest reaching x of each diagonal, forward and backward.
+    let length = old_line_list.len() + new_line_list.len() + 2;
+    let mut forward_list = vec![0; 2 * length + 1];
+    let mut backward_list = vec![0; 2 * length + 1];
+    let mut diff = Vec::with_capacity(length);
+    diff_range(
+        &old_line_list,
+        &new_line_list,
+        &mut forward_list,
+        &mut backward_list,
+        &mut diff,
+    );
+    // Removed lines of each change come first, like in other diffs:
+    let mut start = 0;
+    while start < diff.len() {
+        let change_length = diff[start..]
+            .iter()
+            .take_while(|(kind, _)| *kind != ' ')
+            .count();
+        diff[start..start + change_length].sort_by_key(|(kind, _)| *kind != '-');
+        start += change_length + 1;
+    }
+    diff
+}
+
+/// Appends changes of `new` compared to `old` to `diff` by splitting both at a middle snake.
+fn diff_range<'a>(
+    old: &[&'a str],
+    new: &[&'a str],
+    forward_list: &mut [usize],
+    backward_list: &mut [usize],
+    diff: &mut Vec<(char, &'a str)>,
+) {
+    let prefix_length = old
+        .iter()
+        .zip(new.iter())
+        .take_while(|(old_line, new_line)| old_line == new_line)
+        .count();
+    diff.extend(old[..prefix_length].iter().map(|line| (' ', *line)));
+    let (old, new) = (&old[prefix_length..], &new[prefix_length..]);
+    let suffix_length = old
+        .iter()
+        .rev()
+        .zip(new.iter().rev())
+        .take_while(|(old_line, new_line)| old_line == new_line)
+        .count();
+    let suffix = &old[old.len() - suffix_length..];
+    let (old, new) = (
+        &old[..old.len() - suffix_length],
+        &new[..new.len() - suffix_length],
+    );
+    if old.is_empty() || new.is_empty() {
+        diff.extend(old.iter().map(|line| ('-', *line)));
+        diff.extend(new.iter().map(|line| ('+', *line)));
+    } else {
+        let (old_split, new_split) = middle_snake(old, new, forward_list, backward_list);
+        diff_range(
+            &old[..old_split],
+            &new[..new_split],
+            forward_list,
+            backward_list,
+            diff,
+        );
+        diff_range(
+            &old[old_split..],
+            &new[new_split..],
+            forward_list,
+            backward_list,
+            diff,
+        );
+    }
+    diff.extend(suffix.iter().map(|line| (' ', *line)));
+}
+
+/// Point where the shortest edit script of non-empty `old` and `new`, without common prefix and
+/// suffix, can be split into two shorter ones.
+fn middle_snake(
+    old: &[&str],
+    new: &[&str],
+    forward_list: &mut [usize],
+    backward_list: &mut [usize],
+) -> (usize, usize) {
+    let (old_length, new_length) = (old.len() as isize, new.len() as isize);
+    let delta = old_length - new_length;
+    let is_odd = delta % 2 != 0;
+    // Diagonal `k` (x - y) is stored at `k + offset`:
+    let offset = (old.len() + new.len() + 2) as isize;
+    let index = |k: isize| (k + offset) as usize;
+    forward_list[index(1)] = 0;
+    backward_list[index(1)] = 0;
+    for d in 0..=(old_length + new_length + 1) / 2 {
+        for k in (-d..=d).rev().step_by(2) {
+            let mut x =
+                if k == -d || (k != d && forward_list[index(k - 1)] < forward_list[index(k + 1)]) {
+                    forward_list[index(k + 1)]
+                } else {
+                    forward_list[index(k - 1)] + 1
+                } as isize;
+            let (snake_x, snake_y) = (x, x - k);
+            while x < old_length && x - k < new_length && old[x as usize] == new[(x - k) as usize] {
+                x += 1;
+            }
+            forward_list[index(k)] = x as usize;
+            if is_odd
+                && (k - delta).abs() < d
+                && x + backward_list[index(delta - k)] as isize >= old_length
+            {
+                return (snake_x as usize, snake_y as usize);
+            }
+        }
+        for k in (-d..=d).rev().step_by(2) {
+            let mut x = if k == -d
+                || (k != d && backward_list[index(k - 1)] < backward_list[index(k + 1)])
+            {
+                backward_list[index(k + 1)]
+            } else {
+                backward_list[index(k - 1)] + 1
+            } as isize;
+            while x < old_length
+                && x - k < new_length
+                && old[(old_length - x - 1) as usize] == new[(new_length - x + k - 1) as usize]
+            {
+                x += 1;
+            }
+            backward_list[index(k)] = x as usize;
+            if !is_odd
+                && (k - delta).abs() <= d
+                && x + forward_list[index(delta - k)] as isize >= old_length
+            {
+                return ((old_length - x) as usize, (new_length - x + k) as usize);
+            }
+        }
+    }
+    unreachable!("forward and backward paths always overlap")
+}
+
+/// Diff of `old` and `new` in unified format, or an empty string if they are the same.
+fn unified_diff(old: &str, new: &str, old_name: &str, new_name: &str) -> String {
+    let diff = diff_line_list(old, new);
+    let change_index_list: Vec<_> = diff
+        .iter()
+        .enumerate()
+        .filter(|(_, (kind, _))| *kind != ' ')
+        .map(|(index, _)| index)
+        .collect();
+    if change_index_list.is_empty() {
+        return String::new();
+    }
+    // Changes that are close to each other share a hunk:
+    let mut hunk_list: Vec<(usize, usize)> = Vec::new();
+    for index in change_index_list {
+        let start = index.saturating_sub(DIFF_CONTEXT_LINE_COUNT);
+        let end = (index + DIFF_CONTEXT_LINE_COUNT + 1).min(diff.len());
+        match hunk_list.last_mut() {
+            Some((_, last_end)) if start <= *last_end => *last_end = end,
+            _ => hunk_list.push((start, end)),
+        }
+    }
+    let mut text = format!("--- {}\n+++ {}\n", old_name, new_name);
+    for (start, end) in hunk_list {
+        // Line numbers of the first line of the hunk in each file:
+        let old_start = diff[..start]
+            .iter()
+            .filter(|(kind, _)| *kind != '+')
+            .count()
+            + 1;
+        let new_start = diff[..start]
+            .iter()
+            .filter(|(kind, _)| *kind != '-')
+            .count()
+            + 1;
+        let old_count = diff[start..end]
+            .iter()
+            .filter(|(kind, _)| *kind != '+')
+            .count();
+        let new_count = diff[start..end]
+            .iter()
+            .filter(|(kind, _)| *kind != '-')
+            .count();
+        text.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            old_start, old_count, new_start, new_count
+        ));
+        diff[start..end]
+            .iter()
+            .for_each(|(kind, line)| text.push_str(&format!("{}{}\n", kind, line)));
+    }
+    text
+}
+
+fn describe_rule(rule: &ConfigMatch) -> String {
+    [
+        ("name", &rule.name),
+        ("hostname", &rule.hostname),
+        ("tag", &rule.tag),
+    ]
+    .iter()
+    .filter_map(|(key, maybe_value)| {
+        maybe_value
+            .as_ref()
+            .map(|value| format!("{} = {:?}", key, value))
+    })
+    .collect::<Vec<_>>()
+    .join(" and ")
+}
+
+fn annotate_origin(
+    table: &mut Table,
+    path: String,
+    origin_map: &BTreeMap<String, PathBuf>,
+    filename: &Path,
+) {
+    for (key, item) in table.iter_mut() {
+        let key_path = if path.is_empty() {
+            key.get().to_string()
+        } else {
+            format!("{}.{}", path, key.get())
+        };
+        match item {
+            Item::Table(table) => annotate_origin(table, key_path, origin_map, filename),
+            Item::ArrayOfTables(array) => array
+                .iter_mut()
+                .for_each(|table| annotate_origin(table, key_path.clone(), origin_map, filename)),
+            Item::Value(value) => {
+                let filename = settings::origin(origin_map, &key_path, filename);
+                value
+                    .decor_mut()
+                    .set_suffix(format!(" # {}", filename.display()));
+            }
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diff(old: &str, new: &str) -> String {
+        diff_line_list(old, new)
+            .into_iter()
+            .map(|(kind, line)| format!("{}{}\n", kind, line))
+            .collect()
+    }
+
+    #[test]
+    fn diff_same_and_empty() {
+        assert_eq!(diff("", ""), "");
+        assert_eq!(diff("a\nb", "a\nb\n"), " a\n b\n");
+        assert_eq!(diff("", "a\nb"), "+a\n+b\n");
+        assert_eq!(diff("a\nb", ""), "-a\n-b\n");
+    }
+
+    #[test]
+    fn diff_changes() {
+        assert_eq!(diff("a\nb\nc", "a\nx\nc"), " a\n-b\n+x\n c\n");
+        assert_eq!(diff("a\nb\nc", "b\nc\nd"), "-a\n b\n c\n+d\n");
+        assert_eq!(diff("a\nb\nc\nd", "d\nc\nb\na").matches(' ').count(), 1);
+    }
+
+    #[test]
+    fn diff_is_shortest() {
+        let old = "a\nb\nc\na\nb\nb\na";
+        let new = "c\nb\na\nb\na\nc";
+        let diff_list = diff_line_list(old, new);
+        let kept = |kind| {
+            diff_list
+                .iter()
+                .filter(move |(other, _)| *other != kind)
+                .map(|(_, line)| *line)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(kept('+'), old.lines().collect::<Vec<_>>());
+        assert_eq!(kept('-'), new.lines().collect::<Vec<_>>());
+        // The example of Myers' paper has 5 changes at least:
+        assert_eq!(diff_list.iter().filter(|(kind, _)| *kind != ' ').count(), 5);
+    }
+
+    #[test]
+    fn unified_diff_hunks() {
+        let old = (1..=20).map(|n| format!("{}\n", n)).collect::<String>();
+        let new = old.replace("\n2\n", "\ntwo\n").replace("19\n", "");
+        assert_eq!(unified_diff(&old, &old, "a", "b"), "");
+        assert_eq!(
+            unified_diff(&old, &new, "a", "b"),
+            "--- a\n+++ b\n\
+             @@ -1,5 +1,5 @@\n 1\n-2\n+two\n 3\n 4\n 5\n\
+             @@ -16,5 +16,4 @@\n 16\n 17\n 18\n-19\n 20\n"
+        );
+    }
+}
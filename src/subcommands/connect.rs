@@ -0,0 +1,40 @@
+use crate::error::AppError;
+use crate::settings::{Settings, SubCommand};
+use crate::subcommands::select::run_script;
+use anyhow::Result;
+
+pub fn run(settings: &mut Settings) -> Result<(), AppError> {
+    let (name, maybe_username) =
+        if let SubCommand::Connect { ref name, ref user } = settings.subcommand {
+            (name.clone(), user.clone())
+        } else {
+            // It's already checked in main.rs
+            unreachable!()
+        };
+    let config = settings.try_load_and_set_servers()?;
+    settings.ensure_script_file()?;
+    let server = config
+        .servers
+        .get(&name)
+        .cloned()
+        .ok_or_else(|| AppError::ServerNotFound { name: name.clone() })?;
+    let username = match maybe_username {
+        Some(username) if server.username_list.contains(&username) => username,
+        Some(username) => {
+            return Err(AppError::UsernameNotFound {
+                server_name: name,
+                username,
+                username_list: server.username_list,
+            })
+        }
+        None => server.username_list[0].clone(),
+    };
+    if settings.skip_select {
+        println!(
+            "You have selected `{}` ({}@{}). Skip running script file.",
+            server.name, username, server.hostname
+        );
+        return Ok(());
+    }
+    run_script(settings, server, username)
+}
@@ -1,21 +1,181 @@
+use crate::settings::{sibling_filename, write_copy};
 use crate::utils::run_command;
+use crate::validation::{self, Severity};
 use crate::{error::AppError, settings::Settings};
 use anyhow::Result;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
 use std::time::Instant;
-use tracing::debug;
+use tracing::{debug, info};
 
+/// Editors that open a file at a line with a `+<LINE>` argument before the filename.
+const LINE_ARGUMENT_EDITOR_LIST: &[&str] = &["vi", "vim", "nvim", "nano", "emacs"];
+
+/// What to do with an edited copy that has errors.
+enum Answer {
+    Edit,
+    Discard,
+}
+
+/// Edits a copy of configuration file (like `visudo`), so configuration file is only replaced
+/// when the copy is valid.
 pub fn run(settings: &mut Settings) -> Result<(), AppError> {
     settings.maybe_try_create_configuration_file()?;
     settings.check_editor_command()?;
-    let command = settings.editor_command.clone();
-    let argument_list = settings.editor_argument_list.clone();
-    let start_time = Instant::now();
-    let _ = run_command("Editor", command, argument_list, Vec::new())?;
-    let duration = start_time.elapsed().as_secs();
-    debug!(edit_duration = duration);
-    if duration < 2 {
-        return Err(AppError::EditorFastStop);
+    let configuration =
+        fs::read_to_string(settings.configuration_file.clone()).map_err(|error| {
+            AppError::FileRead {
+                title: "configuration",
+                filename: settings.configuration_file.clone(),
+                source: error,
+            }
+        })?;
+    let edit_filename = sibling_filename(
+        &settings.configuration_file,
+        &format!("edit-{}", process::id()),
+    );
+    write_copy(&edit_filename, &configuration, &settings.configuration_file).map_err(|error| {
+        AppError::FileWrite {
+            title: "temporary configuration",
+            filename: edit_filename.clone(),
+            source: error,
+        }
+    })?;
+    let result = edit_until_valid(settings, &edit_filename, &configuration);
+    // Changes that could neither be written nor discarded are kept for the user:
+    if !matches!(result, Err(AppError::ConfigEditInvalid { .. })) {
+        let _ = fs::remove_file(edit_filename);
     }
+    result?;
     settings.try_load_and_set_configuration()?;
     Ok(())
 }
+
+fn edit_until_valid(
+    settings: &mut Settings,
+    edit_filename: &Path,
+    original_configuration: &str,
+) -> Result<(), AppError> {
+    let mut maybe_line = None;
+    loop {
+        let start_time = Instant::now();
+        run_editor(settings, edit_filename, maybe_line)?;
+        let duration = start_time.elapsed().as_secs();
+        debug!(edit_duration = duration);
+        let mut configuration = read(edit_filename)?;
+        if duration < 2 && configuration == original_configuration {
+            if !atty::is(atty::Stream::Stdin) {
+                return Err(AppError::EditorFastStop);
+            }
+            eprint!("Editor exited immediately, maybe it opened the file in another window.\nPress Enter after you saved {:?}: ", edit_filename);
+            let _ = io::stderr().flush();
+            let _ = io::stdin().read_line(&mut String::new());
+            configuration = read(edit_filename)?;
+        }
+        if configuration == original_configuration {
+            info!("Configuration is not changed");
+            return Ok(());
+        }
+        let layer_list = settings.try_read_configuration_layers(configuration.clone())?;
+        let (_, diagnostic_list) = validation::validate(layer_list, &settings.configuration_file);
+        // Problems of other layers are not for the user to fix here, and if they break loading
+        // configuration, writing it fails below anyway:
+        let error_list: Vec<_> = diagnostic_list
+            .into_iter()
+            .filter(|diagnostic| {
+                diagnostic.severity == Severity::Error
+                    && diagnostic.file == settings.configuration_file
+            })
+            .collect();
+        let problem_list = if error_list.is_empty() {
+            match settings.try_write_configuration(configuration) {
+                Ok(_) => return Ok(()),
+                Err(error) if error.is_configuration_error() => [error.to_string()].to_vec(),
+                Err(error) => return Err(error),
+            }
+        } else {
+            error_list
+                .iter()
+                .map(|diagnostic| diagnostic.to_string())
+                .collect()
+        };
+        eprintln!("Configuration file is not changed because the edited copy has errors:");
+        problem_list
+            .iter()
+            .for_each(|problem| eprintln!("  {}", problem));
+        match ask(edit_filename, problem_list.len())? {
+            Answer::Edit => {
+                maybe_line = error_list.first().and_then(|diagnostic| diagnostic.line);
+            }
+            Answer::Discard => {
+                eprintln!("Discarded changes.");
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn run_editor(
+    settings: &Settings,
+    edit_filename: &Path,
+    maybe_line: Option<usize>,
+) -> Result<(), AppError> {
+    // Editor arguments already contain configuration file, so it's replaced with the copy:
+    let configuration_filename = settings.configuration_file.to_string_lossy().to_string();
+    let edit_filename = edit_filename.to_string_lossy().to_string();
+    let supports_line = settings
+        .editor_command
+        .file_stem()
+        .is_some_and(|command| LINE_ARGUMENT_EDITOR_LIST.contains(&&*command.to_string_lossy()));
+    let mut argument_list = Vec::new();
+    for argument in settings.editor_argument_list.iter() {
+        let argument = argument
+            .to_string_lossy()
+            .replace(&configuration_filename, &edit_filename);
+        if let (Some(line), true) = (maybe_line, supports_line && argument == edit_filename) {
+            argument_list.push(PathBuf::from(format!("+{}", line)));
+        }
+        argument_list.push(PathBuf::from(argument));
+    }
+    let _ = run_command(
+        "Editor",
+        settings.editor_command.clone(),
+        argument_list,
+        Vec::new(),
+    )?;
+    Ok(())
+}
+
+fn ask(edit_filename: &Path, error_count: usize) -> Result<Answer, AppError> {
+    let keep_error = || AppError::ConfigEditInvalid {
+        filename: edit_filename.to_path_buf(),
+        error_count,
+    };
+    if !atty::is(atty::Stream::Stdin) {
+        return Err(keep_error());
+    }
+    loop {
+        eprint!("What now? (e)dit again or (d)iscard changes [e]: ");
+        let _ = io::stderr().flush();
+        let mut answer = String::new();
+        match io::stdin().read_line(&mut answer) {
+            Ok(0) | Err(_) => return Err(keep_error()),
+            Ok(_) => (),
+        }
+        match answer.trim().to_lowercase().as_str() {
+            "" | "e" | "edit" => return Ok(Answer::Edit),
+            "d" | "discard" => return Ok(Answer::Discard),
+            _ => continue,
+        }
+    }
+}
+
+fn read(filename: &Path) -> Result<String, AppError> {
+    fs::read_to_string(filename).map_err(|error| AppError::FileRead {
+        title: "temporary configuration",
+        filename: filename.to_path_buf(),
+        source: error,
+    })
+}
@@ -0,0 +1,84 @@
+use crate::error::AppError;
+use crate::settings::{write_atomically, Settings, SubCommand};
+use crate::ssh_config;
+use anyhow::Result;
+use clap::Parser;
+use std::{fs, path::PathBuf};
+use tracing::info;
+
+#[derive(Debug, Clone, PartialEq, Parser)]
+pub enum Exports {
+    /// Print servers as an OpenSSH client configuration.
+    SshConfig {
+        /// Write servers into a managed section of --file instead of printing them.
+        ///
+        /// The section is regenerated in place on each run and other entries are kept untouched.
+        #[arg(short, long)]
+        write: bool,
+        /// OpenSSH client configuration file.
+        #[arg(short, long, default_value = ssh_config::default_ssh_config_filename())]
+        file: PathBuf,
+    },
+}
+
+pub fn run(settings: &mut Settings) -> Result<(), AppError> {
+    if let SubCommand::Export(ref export) = settings.subcommand {
+        match export.clone() {
+            Exports::SshConfig { write, file } => export_ssh_config(settings, write, file),
+        }
+    } else {
+        // It's already checked in main.rs
+        unreachable!()
+    }
+}
+
+fn export_ssh_config(
+    settings: &mut Settings,
+    write: bool,
+    filename: PathBuf,
+) -> Result<(), AppError> {
+    let config = settings.try_load_and_set_servers()?;
+    let server_list: Vec<_> = config.servers.into_values().collect();
+    let (section, exported_count) = ssh_config::render(&server_list);
+    if !write {
+        print!("{}", section);
+        return Ok(());
+    }
+    let ssh_config = if filename.exists() {
+        fs::read_to_string(filename.clone()).map_err(|error| AppError::FileRead {
+            title: "SSH configuration",
+            filename: filename.clone(),
+            source: error,
+        })?
+    } else {
+        if let Some(directory) = filename.parent() {
+            fs::create_dir_all(directory).map_err(|error| AppError::FileWrite {
+                title: "SSH configuration",
+                filename: filename.clone(),
+                source: error,
+            })?;
+        }
+        String::new()
+    };
+    // A crash halfway must not leave a truncated SSH configuration behind:
+    write_atomically(
+        &filename,
+        &ssh_config::replace_managed_section(&ssh_config, &section),
+    )
+    .map_err(|error| AppError::FileWrite {
+        title: "SSH configuration",
+        filename: filename.clone(),
+        source: error,
+    })?;
+    info!(filename = ?filename, "Updated managed section of SSH configuration file");
+    let skipped_count = server_list.len() - exported_count;
+    if skipped_count > 0 {
+        println!(
+            "Exported {} server(s) into {:?} and skipped {} server(s) whose names contain whitespace.",
+            exported_count, filename, skipped_count
+        );
+    } else {
+        println!("Exported {} server(s) into {:?}.", exported_count, filename);
+    }
+    Ok(())
+}
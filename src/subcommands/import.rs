@@ -0,0 +1,111 @@
+use crate::error::AppError;
+use crate::settings::{Settings, SubCommand, DEFAULT_PORT_NUMBER};
+use crate::ssh_config;
+use crate::subcommands::modify::{group_table_mut, split_name};
+use anyhow::Result;
+use clap::Parser;
+use std::path::PathBuf;
+use toml_edit::{value, Array, Document, Item, Table};
+use tracing::{debug, warn};
+
+#[derive(Debug, Clone, PartialEq, Parser)]
+pub enum Imports {
+    /// Import servers from an OpenSSH client configuration file.
+    SshConfig {
+        /// OpenSSH client configuration file.
+        #[arg(short, long, default_value = ssh_config::default_ssh_config_filename())]
+        file: PathBuf,
+    },
+}
+
+pub fn run(settings: &mut Settings) -> Result<(), AppError> {
+    if let SubCommand::Import(ref import) = settings.subcommand {
+        match import.clone() {
+            Imports::SshConfig { file } => import_ssh_config(settings, file),
+        }
+    } else {
+        // It's already checked in main.rs
+        unreachable!()
+    }
+}
+
+fn import_ssh_config(settings: &mut Settings, filename: PathBuf) -> Result<(), AppError> {
+    let config = settings.try_load_and_set_configuration()?;
+    let server_list = ssh_config::try_read(filename.clone())?;
+    let mut document = config
+        .raw
+        .parse::<Document>()
+        .map_err(|source| AppError::ConfigEdit {
+            filename: settings.configuration_file.clone(),
+            source,
+        })?;
+    let mut imported_name_list = Vec::new();
+    let mut conflict_name_list = Vec::new();
+    for server in server_list {
+        if imported_name_list.contains(&server.name) {
+            debug!(
+                server_name = server.name,
+                "Server is defined more than once, use the first one"
+            );
+            continue;
+        }
+        // Dotted names (e.g. `db.example.com`) are group paths, the same as in `add` subcommand:
+        let path = split_name(&server.name);
+        let Some((key, group_path)) = path.split_last() else {
+            continue;
+        };
+        let maybe_group_table = if config.servers.contains_key(&server.name) {
+            None
+        } else {
+            group_table_mut(&mut document, group_path)
+                .ok()
+                .filter(|group_table| !group_table.contains_key(key))
+        };
+        let Some(group_table) = maybe_group_table else {
+            warn!(
+                server_name = server.name,
+                "Server already exists in configuration file, skipped it"
+            );
+            conflict_name_list.push(server.name);
+            continue;
+        };
+        let mut table = Table::new();
+        if !server.username_list.is_empty() {
+            table["users"] = value(server.username_list.iter().collect::<Array>());
+        }
+        table["hostname"] = value(server.hostname.clone());
+        if server.port != DEFAULT_PORT_NUMBER {
+            table["port"] = value(server.port as i64);
+        }
+        if let Some(identity_file) = &server.identity_file {
+            table["identity_file"] = value(identity_file.clone());
+        }
+        if let Some(proxy_jump) = &server.proxy_jump {
+            table["proxy_jump"] = value(proxy_jump.clone());
+        }
+        if let Some(forward_agent) = server.forward_agent {
+            table["forward_agent"] = value(forward_agent);
+        }
+        group_table.insert(key, Item::Table(table));
+        imported_name_list.push(server.name);
+    }
+    if !imported_name_list.is_empty() {
+        settings.try_write_configuration(document.to_string())?;
+    }
+    println!(
+        "Imported {} server(s) from {:?} into {:?}.",
+        imported_name_list.len(),
+        filename,
+        settings.configuration_file
+    );
+    if !conflict_name_list.is_empty() {
+        println!(
+            "Skipped {} server(s) that already exist in configuration file:",
+            conflict_name_list.len()
+        );
+        conflict_name_list
+            .iter()
+            .for_each(|name| println!("  {}", name));
+    }
+    Ok(())
+}
@@ -0,0 +1,189 @@
+use crate::error::AppError;
+use crate::settings::{ConfigServer, Settings, SubCommand};
+use anyhow::Result;
+use clap::ValueEnum;
+use serde_json::{json, Map, Value};
+use std::io::{self, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, ValueEnum)]
+pub enum ListFormat {
+    /// Aligned columns for humans.
+    #[default]
+    Table,
+    /// An array of objects.
+    Json,
+    /// Comma-separated values with a header row.
+    Csv,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ListField {
+    Name,
+    Hostname,
+    Port,
+    Users,
+    Description,
+}
+
+impl ListField {
+    pub fn all() -> Vec<Self> {
+        [
+            Self::Name,
+            Self::Hostname,
+            Self::Port,
+            Self::Users,
+            Self::Description,
+        ]
+        .to_vec()
+    }
+
+    fn title(&self) -> &'static str {
+        match self {
+            Self::Name => "name",
+            Self::Hostname => "hostname",
+            Self::Port => "port",
+            Self::Users => "users",
+            Self::Description => "description",
+        }
+    }
+
+    fn to_json(self, server: &ConfigServer) -> Value {
+        match self {
+            Self::Name => json!(server.name),
+            Self::Hostname => json!(server.hostname),
+            Self::Port => json!(server.port),
+            Self::Users => json!(server.username_list),
+            Self::Description => json!(server.description),
+        }
+    }
+
+    fn to_text(self, server: &ConfigServer) -> String {
+        match self {
+            Self::Name => server.name.clone(),
+            Self::Hostname => server.hostname.clone(),
+            Self::Port => server.port.to_string(),
+            Self::Users => server.username_list.join(" "),
+            Self::Description => server.description.clone(),
+        }
+    }
+}
+
+pub fn run(settings: &mut Settings) -> Result<(), AppError> {
+    let (format, field_list) = if let SubCommand::List {
+        format,
+        ref field_list,
+    } = settings.subcommand
+    {
+        let field_list = if field_list.is_empty() {
+            ListField::all()
+        } else {
+            field_list.clone()
+        };
+        (format, field_list)
+    } else {
+        // It's already checked in main.rs
+        unreachable!()
+    };
+    let config = settings.try_load_and_set_servers()?;
+    let mut server_list: Vec<_> = config.servers.into_values().collect();
+    server_list.sort_by_key(|server| server.name.clone());
+    let output = match format {
+        ListFormat::Table => render_table(&server_list, &field_list),
+        ListFormat::Json => render_json(&server_list, &field_list),
+        ListFormat::Csv => render_csv(&server_list, &field_list),
+    };
+    // Output is usually piped to other tools that may exit before reading all of it:
+    match io::stdout().write_all(output.as_bytes()) {
+        Err(error) if error.kind() != io::ErrorKind::BrokenPipe => {
+            Err(AppError::StdoutWrite { source: error })
+        }
+        _ => Ok(()),
+    }
+}
+
+fn render_table(server_list: &[ConfigServer], field_list: &[ListField]) -> String {
+    let mut row_list = [field_list
+        .iter()
+        .map(|field| field.title().to_uppercase())
+        .collect::<Vec<_>>()]
+    .to_vec();
+    server_list.iter().for_each(|server| {
+        row_list.push(
+            field_list
+                .iter()
+                // Multi-line descriptions would break the table, so only the first line is shown:
+                .map(|field| {
+                    field
+                        .to_text(server)
+                        .lines()
+                        .next()
+                        .unwrap_or_default()
+                        .to_string()
+                })
+                .collect(),
+        )
+    });
+    let width_list: Vec<_> = (0..field_list.len())
+        .map(|column| {
+            row_list
+                .iter()
+                .map(|row| row[column].chars().count())
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+    row_list
+        .iter()
+        .map(|row| {
+            let line = row
+                .iter()
+                .zip(width_list.iter())
+                .map(|(text, width)| format!("{:width$}", text, width = width))
+                .collect::<Vec<_>>()
+                .join("  ");
+            format!("{}\n", line.trim_end())
+        })
+        .collect()
+}
+
+fn render_json(server_list: &[ConfigServer], field_list: &[ListField]) -> String {
+    let server_list: Vec<_> = server_list
+        .iter()
+        .map(|server| {
+            Value::Object(
+                field_list
+                    .iter()
+                    .map(|field| (field.title().to_string(), field.to_json(server)))
+                    .collect::<Map<_, _>>(),
+            )
+        })
+        .collect();
+    serde_json::to_string_pretty(&Value::Array(server_list)).unwrap() + "\n"
+}
+
+fn render_csv(server_list: &[ConfigServer], field_list: &[ListField]) -> String {
+    let mut output = field_list
+        .iter()
+        .map(|field| field.title())
+        .collect::<Vec<_>>()
+        .join(",")
+        + "\n";
+    server_list.iter().for_each(|server| {
+        output += field_list
+            .iter()
+            .map(|field| csv_escape(field.to_text(server)))
+            .collect::<Vec<_>>()
+            .join(",")
+            .as_str();
+        output += "\n";
+    });
+    output
+}
+
+fn csv_escape(text: String) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text
+    }
+}
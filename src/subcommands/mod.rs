@@ -1,5 +1,12 @@
+pub mod check;
 pub mod config;
+pub mod connect;
 pub mod edit;
+pub mod export;
+pub mod import;
+pub mod list;
+pub mod modify;
 pub mod sample;
 pub mod script;
 pub mod select;
+pub mod trust;
@@ -0,0 +1,485 @@
+use crate::error::AppError;
+use crate::expand;
+use crate::settings::{Config, Settings, SubCommand};
+use crate::validation::SERVER_KEY_LIST;
+use anyhow::Result;
+use toml_edit::{value, Array, Document, InlineTable, Item, Table, TableLike, Value};
+
+/// Fields of a new server.
+#[derive(Debug, Clone, Default)]
+pub struct NewServer {
+    pub hostname: String,
+    pub username_list: Vec<String>,
+    pub port: Option<u16>,
+    pub description: String,
+}
+
+pub fn run(settings: &mut Settings) -> Result<(), AppError> {
+    settings.try_load_and_set_configuration()?;
+    match settings.subcommand.clone() {
+        SubCommand::Add {
+            name,
+            hostname,
+            user_list,
+            port,
+            description,
+        } => {
+            let server = NewServer {
+                hostname,
+                username_list: user_list,
+                port,
+                description: description.unwrap_or_default(),
+            };
+            try_add(settings, &name, &server)?;
+            println!("Added server {:?}.", name);
+        }
+        SubCommand::Remove { name } => {
+            try_remove(settings, &name)?;
+            println!("Removed server {:?}.", name);
+        }
+        SubCommand::Rename { name, new_name } => {
+            try_rename(settings, &name, &new_name)?;
+            println!("Renamed server {:?} to {:?}.", name, new_name);
+        }
+        SubCommand::Set {
+            name,
+            field,
+            value: maybe_value,
+        } => {
+            try_set(settings, &name, &field, maybe_value.as_deref())?;
+            match maybe_value {
+                Some(value) => println!("Set {} of server {:?} to {:?}.", field, name, value),
+                None => println!("Removed {} of server {:?}.", field, name),
+            }
+        }
+        // It's already checked in main.rs
+        _ => unreachable!(),
+    };
+    Ok(())
+}
+
+/// Adds a server to configuration file. Dotted names (e.g. `prod.web1`) add it to a group.
+pub fn try_add(
+    settings: &mut Settings,
+    name: &str,
+    server: &NewServer,
+) -> Result<Config, AppError> {
+    let edit_error = |reason: &str| AppError::ServerEdit {
+        name: name.to_string(),
+        reason: reason.to_string(),
+    };
+    if server.hostname.trim().is_empty() {
+        return Err(edit_error("hostname is empty"));
+    }
+    if server.port == Some(0) {
+        return Err(edit_error("port must be between 1 and 65535"));
+    }
+    if settings.configuration.servers.contains_key(name) {
+        return Err(edit_error("server already exists"));
+    }
+    let path = split_name(name);
+    try_edit(settings, |document| {
+        let (key, group_path) = path
+            .split_last()
+            .ok_or_else(|| edit_error("name is empty"))?;
+        let group_table =
+            group_table_mut(document, group_path).map_err(|reason| edit_error(&reason))?;
+        if group_table.contains_key(key) {
+            return Err(edit_error(
+                "a server or a group with this name already exists",
+            ));
+        }
+        let mut table = Table::new();
+        if !server.username_list.is_empty() {
+            table["users"] = value(server.username_list.iter().collect::<Array>());
+        }
+        table["hostname"] = value(server.hostname.clone());
+        if let Some(port) = server.port {
+            table["port"] = value(port as i64);
+        }
+        if !server.description.is_empty() {
+            table["description"] = value(server.description.clone());
+        }
+        group_table.insert(key, Item::Table(table));
+        Ok(())
+    })
+}
+
+/// Removes a server from configuration file, and its groups if they become empty.
+pub fn try_remove(settings: &mut Settings, name: &str) -> Result<Config, AppError> {
+    let path = try_server_path(settings, name)?;
+    try_edit(settings, |document| {
+        remove_path(document.as_table_mut(), &path);
+        Ok(())
+    })
+}
+
+/// Renames a server. Its table keeps its place in configuration file unless it moves to another
+/// group.
+pub fn try_rename(settings: &mut Settings, name: &str, new_name: &str) -> Result<Config, AppError> {
+    try_update(settings, name, new_name, &[])
+}
+
+/// Sets a field of a server, or removes it if `maybe_value` is `None`. `ssh_options.<KEY>` sets
+/// one of `ssh_options`.
+pub fn try_set(
+    settings: &mut Settings,
+    name: &str,
+    field: &str,
+    maybe_value: Option<&str>,
+) -> Result<Config, AppError> {
+    try_update(settings, name, name, &[(field, maybe_value)])
+}
+
+/// Sets (or removes) several fields of a server and renames it to `new_name` at once, so either
+/// all of the changes are written or none of them.
+pub fn try_update(
+    settings: &mut Settings,
+    name: &str,
+    new_name: &str,
+    field_list: &[(&str, Option<&str>)],
+) -> Result<Config, AppError> {
+    let edit_error = |reason: String| AppError::ServerEdit {
+        name: name.to_string(),
+        reason,
+    };
+    let change_list = field_list
+        .iter()
+        .map(|(field, maybe_value)| parse_change(field, *maybe_value))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(edit_error)?;
+    let path = try_server_path(settings, name)?;
+    let maybe_new_path = if new_name != name {
+        Some(try_new_path(settings, name, new_name, &path)?)
+    } else {
+        None
+    };
+    try_edit(settings, |document| {
+        let item = item_mut(document.as_table_mut(), &path)
+            .ok_or_else(|| edit_error("server table not found".to_string()))?;
+        for change in change_list {
+            set_field(item, change).map_err(edit_error)?;
+        }
+        // Standard tables keep their formatting, but new values of inline tables need spacing:
+        if let Some(table) = item.as_inline_table_mut() {
+            table.fmt();
+        }
+        if let Some(new_path) = maybe_new_path {
+            let (new_key, new_group_path) = new_path
+                .split_last()
+                .ok_or_else(|| edit_error("new name is empty".to_string()))?;
+            if group_table_mut(document, new_group_path)
+                .map_err(edit_error)?
+                .contains_key(new_key)
+            {
+                return Err(edit_error(format!(
+                    "a server or a group named {:?} already exists",
+                    new_name
+                )));
+            }
+            let item = remove_path(document.as_table_mut(), &path)
+                .ok_or_else(|| edit_error("server table not found".to_string()))?;
+            group_table_mut(document, new_group_path)
+                .map_err(edit_error)?
+                .insert(new_key, item);
+        }
+        Ok(())
+    })
+}
+
+/// Fields that can be written with either name, e.g. `users = [...]` or `username_list = [...]`.
+const FIELD_ALIAS_LIST: &[(&str, &str)] = &[("users", "username_list"), ("tags", "tag_list")];
+
+/// A validated change of one field of a server.
+struct FieldChange<'a> {
+    field: &'a str,
+    maybe_option: Option<&'a str>,
+    maybe_value: Option<Value>,
+}
+
+fn parse_change<'a>(field: &'a str, maybe_value: Option<&str>) -> Result<FieldChange<'a>, String> {
+    let (field, maybe_option) = match field.split_once('.') {
+        Some(("ssh_options", option)) if !option.is_empty() => ("ssh_options", Some(option)),
+        _ => (field, None),
+    };
+    if !SERVER_KEY_LIST.contains(&field) || (field == "ssh_options" && maybe_option.is_none()) {
+        return Err(format!(
+            "unknown field {:?}, expected one of {} or ssh_options.<KEY>",
+            field,
+            SERVER_KEY_LIST
+                .iter()
+                .filter(|key| **key != "ssh_options")
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+    if field == "hostname" && maybe_value.is_none() {
+        return Err("hostname can not be removed".to_string());
+    }
+    Ok(FieldChange {
+        field,
+        maybe_option,
+        maybe_value: maybe_value
+            .map(|raw_value| parse_value(field, raw_value))
+            .transpose()?,
+    })
+}
+
+fn set_field(item: &mut Item, change: FieldChange) -> Result<(), String> {
+    let table = item
+        .as_table_like_mut()
+        .ok_or_else(|| "server is not a table".to_string())?;
+    let FieldChange {
+        field,
+        maybe_option,
+        maybe_value,
+    } = change;
+    match (maybe_option, maybe_value) {
+        (Some(option), maybe_value) => {
+            if table.get("ssh_options").is_none() {
+                table.insert("ssh_options", value(InlineTable::new()));
+            }
+            let option_table = table
+                .get_mut("ssh_options")
+                .and_then(|item| item.as_table_like_mut())
+                .ok_or_else(|| "`ssh_options` is not a table".to_string())?;
+            match maybe_value {
+                Some(option_value) => {
+                    option_table.insert(option, Item::Value(option_value));
+                }
+                None => {
+                    option_table.remove(option);
+                }
+            }
+            if option_table.is_empty() {
+                table.remove("ssh_options");
+            } else if let Some(option_table) = table
+                .get_mut("ssh_options")
+                .and_then(|item| item.as_inline_table_mut())
+            {
+                option_table.fmt();
+            }
+        }
+        (None, Some(mut field_value)) => {
+            // An existing field keeps the name it's written with:
+            let key = alias_list(field)
+                .into_iter()
+                .find(|key| table.contains_key(key))
+                .unwrap_or(field);
+            alias_list(field)
+                .into_iter()
+                .filter(|alias| *alias != key)
+                .for_each(|alias| {
+                    table.remove(alias);
+                });
+            // Keep comments after the old value:
+            if let Some(old_value) = table.get(key).and_then(|item| item.as_value()) {
+                *field_value.decor_mut() = old_value.decor().clone();
+            }
+            table.insert(key, Item::Value(field_value));
+        }
+        (None, None) => {
+            alias_list(field).into_iter().for_each(|alias| {
+                table.remove(alias);
+            });
+        }
+    }
+    Ok(())
+}
+
+/// `field` and its other name, if it has one.
+fn alias_list(field: &str) -> Vec<&str> {
+    FIELD_ALIAS_LIST
+        .iter()
+        .find(|(name, alias)| field == *name || field == *alias)
+        .map_or_else(
+            || [field].to_vec(),
+            |(name, alias)| [*name, *alias].to_vec(),
+        )
+}
+
+/// Parses value of a server field from command line, e.g. `admin,root` for `users`.
+pub fn parse_value(field: &str, raw_value: &str) -> Result<Value, String> {
+    match field {
+        "port" => match raw_value.trim().parse::<u16>() {
+            Ok(port) if port > 0 => Ok(Value::from(port as i64)),
+            _ => Err(format!(
+                "invalid port {:?}, it must be between 1 and 65535",
+                raw_value
+            )),
+        },
+        "forward_agent" => match raw_value.trim() {
+            "true" | "yes" => Ok(Value::from(true)),
+            "false" | "no" => Ok(Value::from(false)),
+            _ => Err(format!(
+                "invalid value {:?}, expected true or false",
+                raw_value
+            )),
+        },
+        "users" | "username_list" | "tags" | "tag_list" => Ok(Value::Array(
+            raw_value
+                .split(',')
+                .map(|item| item.trim())
+                .filter(|item| !item.is_empty())
+                .collect::<Array>(),
+        )),
+        "hostname" if raw_value.trim().is_empty() => Err("hostname is empty".to_string()),
+        _ => Ok(Value::from(raw_value)),
+    }
+}
+
+/// Edits configuration file in place, so comments and formatting of everything else are kept.
+/// Result is validated before it's written.
+pub fn try_edit<F>(settings: &mut Settings, edit: F) -> Result<Config, AppError>
+where
+    F: FnOnce(&mut Document) -> Result<(), AppError>,
+{
+    let mut document = settings
+        .configuration
+        .raw
+        .parse::<Document>()
+        .map_err(|source| AppError::ConfigEdit {
+            filename: settings.configuration_file.clone(),
+            source,
+        })?;
+    edit(&mut document)?;
+    settings.try_write_configuration(document.to_string())
+}
+
+/// Path that the table of server `name` at `path` moves to when it's renamed to `new_name`.
+fn try_new_path(
+    settings: &Settings,
+    name: &str,
+    new_name: &str,
+    path: &[String],
+) -> Result<Vec<String>, AppError> {
+    let edit_error = |reason: String| AppError::ServerEdit {
+        name: name.to_string(),
+        reason,
+    };
+    if settings.configuration.servers.contains_key(new_name) {
+        return Err(edit_error(format!("server {:?} already exists", new_name)));
+    }
+    let server = &settings.configuration.servers[name];
+    let mut new_path = split_name(new_name);
+    // Group of a server with `group` field doesn't come from where its table is:
+    if let Some(group) = server_table(&settings.configuration.raw, path).and_then(|table| {
+        table
+            .get("group")
+            .and_then(|item| item.as_str().map(String::from))
+    }) {
+        let group_path = split_name(&server.group);
+        if !new_path.starts_with(&group_path) || new_path.len() != group_path.len() + 1 {
+            return Err(edit_error(format!(
+                "its group is set to {:?} by its `group` field, so the new name must be {:?}",
+                group,
+                format!("{}.<NAME>", server.group)
+            )));
+        }
+        new_path = path[..path.len() - 1].to_vec();
+        new_path.push(split_name(new_name).pop().unwrap_or_default());
+    }
+    Ok(new_path)
+}
+
+/// Path of the table of a server that can be edited in configuration file itself.
+fn try_server_path(settings: &Settings, name: &str) -> Result<Vec<String>, AppError> {
+    let edit_error = |reason: String| AppError::ServerEdit {
+        name: name.to_string(),
+        reason,
+    };
+    let server =
+        settings
+            .configuration
+            .servers
+            .get(name)
+            .ok_or_else(|| AppError::ServerNotFound {
+                name: name.to_string(),
+            })?;
+    let table = server_table(&settings.configuration.raw, &server.table_path).ok_or_else(|| {
+        edit_error(format!(
+            "it's not defined in configuration file {:?} but in {:?}",
+            settings.configuration_file, server.filename
+        ))
+    })?;
+    if table
+        .get("hostname")
+        .and_then(|item| item.as_str())
+        .is_some_and(expand::is_pattern)
+    {
+        return Err(edit_error(format!(
+            "it's expanded from hostname pattern of {:?}, edit that instead",
+            server.table_path.join(".")
+        )));
+    }
+    Ok(server.table_path.clone())
+}
+
+/// Server table at `path` of `configuration`, as an owned table to look at.
+fn server_table(configuration: &str, path: &[String]) -> Option<toml::Table> {
+    let table = toml::from_str::<toml::Table>(configuration).ok()?;
+    path.iter()
+        .try_fold(&table, |table, key| table.get(key)?.as_table())
+        .filter(|table| table.contains_key("hostname") || table.contains_key("extends"))
+        .cloned()
+}
+
+fn item_mut<'a>(table: &'a mut Table, path: &[String]) -> Option<&'a mut Item> {
+    let (first_key, key_list) = path.split_first()?;
+    key_list
+        .iter()
+        .try_fold(table.get_mut(first_key)?, |item, key| {
+            item.as_table_like_mut()?.get_mut(key)
+        })
+}
+
+/// Group table at `path`, which is created if it doesn't exist.
+pub fn group_table_mut<'a>(
+    document: &'a mut Document,
+    path: &[String],
+) -> Result<&'a mut dyn TableLike, String> {
+    let mut table: &mut dyn TableLike = document.as_table_mut();
+    for key in path {
+        if table.get(key).is_none() {
+            let mut group = Table::new();
+            group.set_implicit(true);
+            table.insert(key, Item::Table(group));
+        }
+        let item = table.get_mut(key).unwrap();
+        let is_server = item
+            .as_table_like()
+            .is_some_and(|table| table.contains_key("hostname") || table.contains_key("extends"));
+        table = match item.as_table_like_mut() {
+            Some(group) if !is_server => group,
+            _ => return Err(format!("{:?} is not a group", key)),
+        };
+    }
+    Ok(table)
+}
+
+/// Removes the item at `path` and then the groups that it leaves empty.
+fn remove_path(table: &mut dyn TableLike, path: &[String]) -> Option<Item> {
+    match path {
+        [] => None,
+        [key] => table.remove(key),
+        [key, rest @ ..] => {
+            let child = table.get_mut(key)?;
+            // Only remove groups that have no table header of their own:
+            let is_implicit = child.as_table().is_none_or(|table| table.is_implicit());
+            let child = child.as_table_like_mut()?;
+            let item = remove_path(child, rest);
+            if child.is_empty() && is_implicit {
+                table.remove(key);
+            }
+            item
+        }
+    }
+}
+
+pub fn split_name(name: &str) -> Vec<String> {
+    name.split('.')
+        .filter(|key| !key.trim().is_empty())
+        .map(|key| key.trim().to_string())
+        .collect()
+}
@@ -1,20 +1,25 @@
 use crate::error::AppError;
-use crate::settings::{ConfigServer, Settings, DEFAULT_PORT_NUMBER};
+use crate::history::History;
+use crate::keymap::{KeyAction, KeyMap};
+use crate::settings::{ConfigServer, Settings, SortMode, DEFAULT_PORT_NUMBER};
 use crate::subcommands::edit::run as edit;
+use crate::subcommands::modify::{parse_value, try_add, try_update, NewServer};
+use crate::theme::Theme;
 use crate::utils::run_command;
 use anyhow::Result;
 use clap::crate_name;
 use crossterm::event::Event::Key;
 use crossterm::{
     event,
-    event::KeyCode,
+    event::{KeyCode, KeyEvent, KeyModifiers},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::path::PathBuf;
-use tracing::debug;
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{debug, warn};
 use tui::layout::Rect;
 use tui::text::Text;
 use tui::widgets::{List, ListItem};
@@ -29,12 +34,18 @@ use tui::{
     Frame, Terminal,
 };
 
+/// How often configuration files are checked for changes while waiting for a key.
+const FILE_WATCH_INTERVAL: Duration = Duration::from_millis(500);
+/// How long a notice (e.g. after reloading configuration) stays visible.
+const NOTICE_DURATION: Duration = Duration::from_secs(3);
+
 #[derive(Debug)]
 enum ControlFlow {
     Stop,
     Edit,
     Selected,
     Reload,
+    RefreshInventory,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -44,10 +55,256 @@ struct State {
     choosing_server: bool,
     choosing_username: bool,
     selected_server: bool,
+    all_server_list: Vec<ConfigServer>,
     server_list: Vec<ConfigServer>,
+    server_match_list: Vec<ServerMatch>,
     server_table_state: TableState,
     username_list_state: ListState,
     working_keys: WorkingKeys,
+    filter: String,
+    filtering: bool,
+    sort_mode: SortMode,
+    last_used: HashMap<String, u64>,
+    use_count: HashMap<String, usize>,
+    row_list: Vec<TableRow>,
+    collapsed_group_set: HashSet<String>,
+    has_inventory: bool,
+    form: Option<ServerForm>,
+    notice: Option<(String, Instant)>,
+}
+
+/// Notices changes of configuration files (written by another editor, or `git pull`) by their
+/// modification time and size.
+#[derive(Debug, Clone, Default)]
+struct FileWatcher {
+    file_list: Vec<(PathBuf, Option<(SystemTime, u64)>)>,
+}
+
+impl FileWatcher {
+    fn new(settings: &Settings) -> Self {
+        let mut watcher = Self::default();
+        watcher.refresh(settings);
+        watcher
+    }
+
+    fn snapshot(settings: &Settings) -> Vec<(PathBuf, Option<(SystemTime, u64)>)> {
+        settings
+            .configuration_file_list()
+            .into_iter()
+            .map(|filename| {
+                let maybe_modified = filename.metadata().ok().and_then(|metadata| {
+                    metadata
+                        .modified()
+                        .ok()
+                        .map(|modified| (modified, metadata.len()))
+                });
+                (filename, maybe_modified)
+            })
+            .collect()
+    }
+
+    /// Forgets changes until now, e.g. after configuration file is written by ourselves.
+    fn refresh(&mut self, settings: &Settings) {
+        self.file_list = Self::snapshot(settings);
+    }
+
+    /// Returns `true` if a file is changed, created or removed since the last check.
+    fn is_changed(&mut self, settings: &Settings) -> bool {
+        let file_list = Self::snapshot(settings);
+        let is_changed = file_list != self.file_list;
+        if is_changed {
+            debug!(before = ?self.file_list, after = ?file_list, "Configuration files changed");
+        }
+        self.file_list = file_list;
+        is_changed
+    }
+}
+
+/// A row of the server table which is either a group of servers or a server of `server_list`.
+#[derive(Debug, Clone, PartialEq)]
+enum TableRow {
+    Group {
+        path: String,
+        depth: usize,
+        server_count: usize,
+    },
+    Server {
+        offset: usize,
+        depth: usize,
+    },
+}
+
+/// Offsets of characters that matched the filter in each column of a server row.
+#[derive(Debug, Clone, Default)]
+struct ServerMatch {
+    name: Vec<usize>,
+    hostname: Vec<usize>,
+    description: Vec<usize>,
+}
+
+/// Fields of the server form in the order they are shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FormField {
+    Name,
+    Hostname,
+    Port,
+    Users,
+    Description,
+}
+
+impl FormField {
+    const ALL: [Self; 5] = [
+        Self::Name,
+        Self::Hostname,
+        Self::Port,
+        Self::Users,
+        Self::Description,
+    ];
+
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Name => "Name",
+            Self::Hostname => "Hostname",
+            Self::Port => "Port",
+            Self::Users => "Users",
+            Self::Description => "Description",
+        }
+    }
+
+    /// Name of the field in configuration file.
+    fn key(&self) -> &'static str {
+        match self {
+            Self::Name => "name",
+            Self::Hostname => "hostname",
+            Self::Port => "port",
+            Self::Users => "users",
+            Self::Description => "description",
+        }
+    }
+}
+
+/// Form for adding a new server, or editing an existing one if `original_name` is set.
+#[derive(Debug, Clone, Default)]
+struct ServerForm {
+    original_name: Option<String>,
+    initial_value_list: [String; 5],
+    value_list: [String; 5],
+    focus: usize,
+    error: Option<String>,
+}
+
+impl ServerForm {
+    /// New server inside `group` if a group is selected.
+    fn add(group: Option<String>) -> Self {
+        let mut form = Self::default();
+        if let Some(group) = group {
+            form.value_list[0] = format!("{}.", group);
+        }
+        form
+    }
+
+    fn edit(server: &ConfigServer) -> Self {
+        let value_list = [
+            server.name.clone(),
+            server.hostname.clone(),
+            server.port.to_string(),
+            server.username_list.join(", "),
+            server.description.clone(),
+        ];
+        Self {
+            original_name: Some(server.name.clone()),
+            initial_value_list: value_list.clone(),
+            value_list,
+            ..Self::default()
+        }
+    }
+
+    fn value(&self, field: FormField) -> &str {
+        &self.value_list[field as usize]
+    }
+
+    fn focused_value_mut(&mut self) -> &mut String {
+        &mut self.value_list[self.focus]
+    }
+
+    fn next_field(&mut self) {
+        self.focus = (self.focus + 1) % FormField::ALL.len();
+    }
+
+    fn previous_field(&mut self) {
+        self.focus = (self.focus + FormField::ALL.len() - 1) % FormField::ALL.len();
+    }
+
+    /// Checks the fields before anything is written, so mistakes point to their field.
+    fn validate(&self) -> Result<(), (FormField, String)> {
+        let name = self.value(FormField::Name).trim();
+        if name.is_empty() {
+            return Err((FormField::Name, "name is empty".to_string()));
+        }
+        if name.chars().any(char::is_whitespace) {
+            return Err((FormField::Name, "name can not contain spaces".to_string()));
+        }
+        if name.split('.').any(str::is_empty) {
+            return Err((FormField::Name, format!("invalid group in name {:?}", name)));
+        }
+        for field in [FormField::Hostname, FormField::Port] {
+            let value = self.value(field);
+            if field == FormField::Hostname || !value.trim().is_empty() {
+                parse_value(field.key(), value).map_err(|reason| (field, reason))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the server to configuration file and returns its name.
+    fn try_save(&self, settings: &mut Settings) -> Result<String, (Option<FormField>, String)> {
+        self.validate()
+            .map_err(|(field, reason)| (Some(field), reason))?;
+        let name = self.value(FormField::Name).trim().to_string();
+        let result = match self.original_name {
+            None => {
+                let server = NewServer {
+                    hostname: self.value(FormField::Hostname).trim().to_string(),
+                    username_list: self
+                        .value(FormField::Users)
+                        .split(',')
+                        .map(|username| username.trim().to_string())
+                        .filter(|username| !username.is_empty())
+                        .collect(),
+                    port: self.value(FormField::Port).trim().parse().ok(),
+                    description: self.value(FormField::Description).to_string(),
+                };
+                try_add(settings, &name, &server)
+            }
+            Some(ref original_name) => {
+                // Only changed fields are written, so values from templates, defaults and match
+                // rules stay where they are:
+                let change_list: Vec<_> = FormField::ALL[1..]
+                    .iter()
+                    .filter(|field| {
+                        self.value(**field) != self.initial_value_list[**field as usize]
+                    })
+                    .map(|field| {
+                        let value = self.value(*field);
+                        let value = if *field == FormField::Description {
+                            value
+                        } else {
+                            value.trim()
+                        };
+                        (field.key(), Some(value).filter(|value| !value.is_empty()))
+                    })
+                    .collect();
+                try_update(settings, original_name, &name, &change_list)
+            }
+        };
+        match result {
+            Ok(_) => Ok(name),
+            Err(error) => Err((
+                None,
+                error_to_string(error, String::new()).trim().to_string(),
+            )),
+        }
+    }
 }
 
 impl PartialEq for State {
@@ -56,147 +313,69 @@ impl PartialEq for State {
             self.choosing_server,
             self.choosing_username,
             self.selected_server,
+            self.filtering,
         ] == [
             other.choosing_server,
             other.choosing_username,
             other.selected_server,
+            other.filtering,
         ] && [self.server_offset, self.username_offset]
             == [other.server_offset, other.username_offset]
-    }
-}
-
-#[derive(Debug, Clone, PartialEq, Copy)]
-struct Theme {
-    main_border_bg: Color,
-    main_border_fg: Color,
-    main_border_title_bg: Color,
-    main_border_title_fg: Color,
-
-    help_text_bg: Color,
-    help_text_fg: Color,
-    help_hint_text_bg: Color,
-    help_hint_text_fg: Color,
-    help_error_text_bg: Color,
-    help_error_text_fg: Color,
-    help_paragraph_bg: Color,
-    help_paragraph_fg: Color,
-
-    table_border_bg: Color,
-    table_border_fg: Color,
-    table_border_active_fg: Color,
-    table_border_title_bg: Color,
-    table_border_title_fg: Color,
-    table_title_bg: Color,
-    table_title_fg: Color,
-    table_row_name_bg: Color,
-    table_row_name_fg: Color,
-    table_row_hostname_bg: Color,
-    table_row_hostname_fg: Color,
-    table_row_description_bg: Color,
-    table_row_description_fg: Color,
-    table_highlight_bg: Color,
-    table_highlight_fg: Color,
-
-    list_border_bg: Color,
-    list_border_fg: Color,
-    list_border_active_fg: Color,
-    list_border_title_bg: Color,
-    list_border_title_fg: Color,
-    username_bg: Color,
-    username_fg: Color,
-    list_highlight_bg: Color,
-    list_highlight_fg: Color,
-
-    help_key_working_bg: Color,
-    help_key_working_fg: Color,
-    help_key_not_working_bg: Color,
-    help_key_not_working_fg: Color,
-    help_key_guide_working_bg: Color,
-    help_key_guide_working_fg: Color,
-    help_key_guide_not_working_bg: Color,
-    help_key_guide_not_working_fg: Color,
-}
-
-impl Default for Theme {
-    fn default() -> Self {
-        Self {
-            main_border_bg: Color::Reset,
-            main_border_fg: Color::White,
-            main_border_title_bg: Color::Reset,
-            main_border_title_fg: Color::White,
-
-            help_text_bg: Color::Reset,
-            help_text_fg: Color::White,
-            help_hint_text_bg: Color::Reset,
-            help_hint_text_fg: Color::White,
-            help_error_text_bg: Color::Reset,
-            help_error_text_fg: Color::Red,
-            help_paragraph_bg: Color::Reset,
-            help_paragraph_fg: Color::Reset,
-
-            table_border_bg: Color::Reset,
-            table_border_fg: Color::Yellow,
-            table_border_active_fg: Color::LightYellow,
-            table_border_title_bg: Color::Reset,
-            table_border_title_fg: Color::Yellow,
-            table_title_bg: Color::Reset,
-            table_title_fg: Color::LightYellow,
-            table_row_name_bg: Color::Reset,
-            table_row_name_fg: Color::White,
-            table_row_hostname_bg: Color::Reset,
-            table_row_hostname_fg: Color::Green,
-            table_row_description_bg: Color::Reset,
-            table_row_description_fg: Color::LightBlue,
-            table_highlight_bg: Color::Reset,
-            table_highlight_fg: Color::Reset,
-
-            list_border_bg: Color::Reset,
-            list_border_fg: Color::Yellow,
-            list_border_active_fg: Color::LightYellow,
-            list_border_title_bg: Color::Reset,
-            list_border_title_fg: Color::Yellow,
-            username_bg: Color::Reset,
-            username_fg: Color::LightBlue,
-            list_highlight_bg: Color::Reset,
-            list_highlight_fg: Color::Reset,
-
-            help_key_working_bg: Color::Reset,
-            help_key_working_fg: Color::LightYellow,
-            help_key_not_working_bg: Color::Reset,
-            help_key_not_working_fg: Color::DarkGray,
-            help_key_guide_working_bg: Color::Reset,
-            help_key_guide_working_fg: Color::White,
-            help_key_guide_not_working_bg: Color::Reset,
-            help_key_guide_not_working_fg: Color::DarkGray,
-        }
+            && self.filter == other.filter
+            && self.sort_mode == other.sort_mode
+            && self.form.is_some() == other.form.is_some()
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Default)]
 pub struct WorkingKeys {
-    pub q: bool,
-    pub e: bool,
-    pub r: bool,
     pub up: bool,
     pub down: bool,
     pub left: bool,
     pub right: bool,
-    pub enter: bool,
+    pub quit: bool,
+    pub edit: bool,
+    pub add_server: bool,
+    pub edit_server: bool,
+    pub reload: bool,
+    pub choose: bool,
+    pub filter: bool,
+    pub clear_filter: bool,
+    pub sort: bool,
+    pub refresh_inventory: bool,
 }
 
 impl WorkingKeys {
-    pub fn to_info_list(&self) -> Vec<(&'static str, &'static str, bool)> {
-        [
-            ("Up", "Previous server/username", self.up),
-            ("Down", "Next server/username", self.down),
-            ("Left", "Choose from servers", self.left),
-            ("Right", "Choose from usernames", self.right),
-            ("q", "Quit", self.q),
-            ("e", "Edit config file", self.e),
-            ("r", "Reload config file", self.r),
-            ("Enter", "Choose", self.enter),
-        ]
-        .to_vec()
+    pub fn is_working(&self, action: KeyAction) -> bool {
+        match action {
+            KeyAction::Up => self.up,
+            KeyAction::Down => self.down,
+            KeyAction::Left => self.left,
+            KeyAction::Right => self.right,
+            KeyAction::Quit => self.quit,
+            KeyAction::Edit => self.edit,
+            KeyAction::AddServer => self.add_server,
+            KeyAction::EditServer => self.edit_server,
+            KeyAction::Reload => self.reload,
+            KeyAction::Choose => self.choose,
+            KeyAction::Filter => self.filter,
+            KeyAction::ClearFilter => self.clear_filter,
+            KeyAction::Sort => self.sort,
+            KeyAction::RefreshInventory => self.refresh_inventory,
+        }
+    }
+
+    pub fn to_info_list(&self, keymap: &KeyMap) -> Vec<(String, &'static str, bool)> {
+        KeyAction::all()
+            .into_iter()
+            .map(|action| {
+                (
+                    keymap.key_names(action),
+                    action.help(),
+                    self.is_working(action),
+                )
+            })
+            .collect()
     }
 }
 
@@ -207,34 +386,315 @@ impl TryFrom<HashMap<String, ConfigServer>> for State {
         let mut state = State::default();
         value
             .into_iter()
-            .for_each(|(_, config)| state.server_list.push(config));
-        state.server_list.sort_by_key(|server| server.name.clone());
-        state.working_keys.q = true;
-        state.working_keys.e = true;
-        state.working_keys.r = true;
-        if !state.server_list.is_empty() {
-            state.working_keys = WorkingKeys {
-                q: true,
-                e: true,
-                r: true,
+            .for_each(|(_, config)| state.all_server_list.push(config));
+        state
+            .all_server_list
+            .sort_by_key(|server| server.name.clone());
+        state.choosing_server = true;
+        state.apply_filter();
+        Ok(state)
+    }
+}
+
+impl State {
+    fn set_sort_mode(&mut self, sort_mode: SortMode, history: &History) {
+        self.last_used = history.last_used();
+        self.use_count = history.count();
+        self.sort_mode = sort_mode;
+        self.sort_servers();
+    }
+
+    fn next_sort_mode(&mut self) {
+        self.sort_mode = self.sort_mode.next();
+        self.sort_servers();
+    }
+
+    fn sort_servers(&mut self) {
+        let last_used = |server: &ConfigServer| self.last_used.get(&server.name).cloned();
+        let use_count = |server: &ConfigServer| self.use_count.get(&server.name).cloned();
+        let mut server_list = self.all_server_list.clone();
+        match self.sort_mode {
+            SortMode::Name => server_list.sort_by_key(|server| server.name.clone()),
+            SortMode::Recent => server_list.sort_by(|x, y| {
+                last_used(y)
+                    .cmp(&last_used(x))
+                    .then_with(|| x.name.cmp(&y.name))
+            }),
+            SortMode::Frequent => server_list.sort_by(|x, y| {
+                use_count(y)
+                    .cmp(&use_count(x))
+                    .then_with(|| last_used(y).cmp(&last_used(x)))
+                    .then_with(|| x.name.cmp(&y.name))
+            }),
+        };
+        self.all_server_list = server_list;
+        // The first server is the most relevant one after sorting:
+        self.server_table_state.select(None);
+        self.apply_filter();
+    }
+
+    /// Rebuilds `server_list` from servers that match the filter.
+    fn apply_filter(&mut self) {
+        let maybe_selected_row = self.selected_row_id();
+        self.server_list.clear();
+        self.server_match_list.clear();
+        for server in self.all_server_list.iter() {
+            let server_match = if self.filter.is_empty() {
+                ServerMatch::default()
+            } else {
+                match (
+                    fuzzy_match(&self.filter, &server.name),
+                    fuzzy_match(&self.filter, &server.hostname),
+                    fuzzy_match(&self.filter, &server.description),
+                ) {
+                    (None, None, None) => continue,
+                    (name, hostname, description) => ServerMatch {
+                        name: name.unwrap_or_default(),
+                        hostname: hostname.unwrap_or_default(),
+                        description: description.unwrap_or_default(),
+                    },
+                }
+            };
+            self.server_list.push(server.clone());
+            self.server_match_list.push(server_match);
+        }
+        self.username_offset = 0;
+        self.username_list_state.select(None);
+        self.choosing_username = false;
+        self.choosing_server = true;
+        self.build_row_list(maybe_selected_row);
+    }
+
+    /// Group path or server name of the selected row.
+    fn selected_row_id(&self) -> Option<(bool, String)> {
+        match self
+            .server_table_state
+            .selected()
+            .and_then(|offset| self.row_list.get(offset))
+        {
+            Some(TableRow::Group { path, .. }) => Some((true, path.clone())),
+            Some(TableRow::Server { offset, .. }) => self
+                .server_list
+                .get(*offset)
+                .map(|server| (false, server.name.clone())),
+            None => None,
+        }
+    }
+
+    /// Rebuilds the tree of groups and servers from `server_list` and keeps the selected row (or
+    /// its closest visible group) selected.
+    ///
+    /// Groups are ordered by their first server in `server_list`, so sort modes apply to them too.
+    fn build_row_list(&mut self, maybe_selected_row: Option<(bool, String)>) {
+        // Children of each group in order, the root group is "":
+        let mut child_map: HashMap<String, Vec<TableRow>> = HashMap::new();
+        let mut server_count_map: HashMap<String, usize> = HashMap::new();
+        for (offset, server) in self.server_list.iter().enumerate() {
+            let group_list: Vec<_> = server
+                .group
+                .split('.')
+                .filter(|group| !group.is_empty())
+                .collect();
+            let mut parent = String::new();
+            for depth in 0..group_list.len() {
+                let path = group_list[..=depth].join(".");
+                *server_count_map.entry(path.clone()).or_insert(0) += 1;
+                if !child_map.contains_key(&path) {
+                    child_map.insert(path.clone(), Vec::new());
+                    child_map.entry(parent).or_default().push(TableRow::Group {
+                        path: path.clone(),
+                        depth,
+                        server_count: 0,
+                    });
+                }
+                parent = path;
+            }
+            child_map.entry(parent).or_default().push(TableRow::Server {
+                offset,
+                depth: group_list.len(),
+            });
+        }
+        // Filtered servers are always visible:
+        let show_all = !self.filter.is_empty();
+        let mut row_list = Vec::new();
+        let mut stack: Vec<TableRow> = child_map.remove("").unwrap_or_default();
+        stack.reverse();
+        while let Some(row) = stack.pop() {
+            if let TableRow::Group { ref path, .. } = row {
+                if show_all || !self.collapsed_group_set.contains(path) {
+                    let mut child_list = child_map.remove(path).unwrap_or_default();
+                    child_list.reverse();
+                    stack.append(&mut child_list);
+                }
+            }
+            row_list.push(match row {
+                TableRow::Group { path, depth, .. } => TableRow::Group {
+                    server_count: server_count_map[&path],
+                    path,
+                    depth,
+                },
+                row => row,
+            });
+        }
+        self.row_list = row_list;
+        let maybe_offset = maybe_selected_row
+            .and_then(|(is_group, name)| {
+                let mut name_list = [name.clone()].to_vec();
+                // If the selected server is inside a collapsed group, select the group:
+                let mut path = if is_group {
+                    name.clone()
+                } else {
+                    self.server_list
+                        .iter()
+                        .find(|server| server.name == name)
+                        .map(|server| server.group.clone())
+                        .unwrap_or_default()
+                };
+                while !path.is_empty() {
+                    name_list.push(path.clone());
+                    path = path
+                        .rsplit_once('.')
+                        .map(|(parent, _)| parent.to_string())
+                        .unwrap_or_default();
+                }
+                name_list.iter().enumerate().find_map(|(index, name)| {
+                    self.row_list.iter().position(|row| match row {
+                        TableRow::Group { path, .. } => (is_group || index > 0) && path == name,
+                        TableRow::Server { offset, .. } => {
+                            !is_group && index == 0 && &self.server_list[*offset].name == name
+                        }
+                    })
+                })
+            })
+            .or(if self.row_list.is_empty() {
+                None
+            } else {
+                Some(0)
+            });
+        self.server_offset = maybe_offset.unwrap_or_default();
+        self.server_table_state.select(maybe_offset);
+        self.update_working_keys();
+    }
+
+    fn current_server(&self) -> Option<&ConfigServer> {
+        match self.row_list.get(self.server_offset) {
+            Some(TableRow::Server { offset, .. }) => self.server_list.get(*offset),
+            _ => None,
+        }
+    }
+
+    fn current_group(&self) -> Option<String> {
+        match self.row_list.get(self.server_offset) {
+            Some(TableRow::Group { path, .. }) => Some(path.clone()),
+            _ => None,
+        }
+    }
+
+    /// Collapses the selected group or the group of the selected server.
+    fn collapse(&mut self) {
+        let maybe_path = self.current_group().or_else(|| {
+            self.current_server()
+                .map(|server| server.group.clone())
+                .filter(|group| !group.is_empty())
+        });
+        if let Some(path) = maybe_path {
+            self.collapsed_group_set.insert(path.clone());
+            self.build_row_list(Some((true, path)));
+        }
+    }
+
+    fn expand(&mut self, path: String) {
+        self.collapsed_group_set.remove(&path);
+        self.build_row_list(Some((true, path)));
+    }
+
+    fn update_working_keys(&mut self) {
+        if self.form.is_some() {
+            // Up and down move between fields of the form, other keys are typed into it:
+            self.working_keys = WorkingKeys {
                 up: true,
                 down: true,
-                left: true,
-                right: true,
-                enter: true,
+                ..WorkingKeys::default()
             };
-            state.choosing_server = true;
-            state.next_server();
+            return;
+        }
+        let has_row = !self.row_list.is_empty();
+        self.working_keys = WorkingKeys {
+            up: has_row,
+            down: has_row,
+            left: has_row && !self.filtering,
+            right: has_row && !self.filtering,
+            quit: !self.filtering,
+            edit: !self.filtering,
+            add_server: !self.filtering,
+            edit_server: has_row && !self.filtering,
+            reload: !self.filtering,
+            choose: has_row,
+            filter: !self.filtering && !self.all_server_list.is_empty(),
+            clear_filter: self.filtering || !self.filter.is_empty(),
+            sort: !self.filtering && !self.all_server_list.is_empty(),
+            refresh_inventory: !self.filtering && self.has_inventory,
+        };
+    }
+
+    /// Replaces servers (e.g. after configuration file is written or reloaded) and keeps the
+    /// selected row if it still exists.
+    fn set_servers(
+        &mut self,
+        servers: HashMap<String, ConfigServer>,
+        maybe_selected_row: Option<(bool, String)>,
+    ) {
+        self.all_server_list = servers.into_values().collect();
+        self.sort_servers();
+        self.build_row_list(maybe_selected_row);
+    }
+
+    fn show_notice(&mut self, notice: &str) {
+        self.notice = Some((notice.to_string(), Instant::now()));
+    }
+
+    fn current_notice(&self) -> Option<&str> {
+        self.notice
+            .as_ref()
+            .filter(|(_, shown_at)| shown_at.elapsed() < NOTICE_DURATION)
+            .map(|(notice, _)| notice.as_str())
+    }
+
+    fn open_form(&mut self, form: ServerForm) {
+        self.form = Some(form);
+        self.update_working_keys();
+    }
+
+    fn close_form(&mut self) {
+        self.form = None;
+        self.update_working_keys();
+    }
+
+    fn start_filtering(&mut self) {
+        self.filtering = true;
+        self.choosing_username = false;
+        self.username_list_state.select(None);
+        self.choosing_server = true;
+        self.update_working_keys();
+    }
+
+    fn stop_filtering(&mut self, clear: bool) {
+        self.filtering = false;
+        if clear {
+            self.filter.clear();
+            self.apply_filter();
+        } else {
+            self.update_working_keys();
         }
-        Ok(state)
     }
-}
 
-impl State {
     pub fn next_server(&mut self) {
+        if self.row_list.is_empty() {
+            return;
+        }
         let offset = match self.server_table_state.selected() {
             Some(offset) => {
-                if offset >= self.server_list.len() - 1 {
+                if offset >= self.row_list.len() - 1 {
                     0
                 } else {
                     offset + 1
@@ -247,10 +707,13 @@ impl State {
     }
 
     pub fn previous_server(&mut self) {
+        if self.row_list.is_empty() {
+            return;
+        }
         let offset = match self.server_table_state.selected() {
             Some(offset) => {
                 if offset == 0 {
-                    self.server_list.len() - 1
+                    self.row_list.len() - 1
                 } else {
                     offset - 1
                 }
@@ -262,9 +725,16 @@ impl State {
     }
 
     fn next_username(&mut self) {
+        let username_count = self
+            .current_server()
+            .map(|server| server.username_list.len())
+            .unwrap_or_default();
+        if username_count == 0 {
+            return;
+        }
         let offset = match self.username_list_state.selected() {
             Some(offset) => {
-                if offset >= self.server_list[self.server_offset].username_list.len() - 1 {
+                if offset >= username_count - 1 {
                     0
                 } else {
                     offset + 1
@@ -277,10 +747,17 @@ impl State {
     }
 
     fn previous_username(&mut self) {
+        let username_count = self
+            .current_server()
+            .map(|server| server.username_list.len())
+            .unwrap_or_default();
+        if username_count == 0 {
+            return;
+        }
         let offset = match self.username_list_state.selected() {
             Some(offset) => {
                 if offset == 0 {
-                    self.server_list[self.server_offset].username_list.len() - 1
+                    username_count - 1
                 } else {
                     offset - 1
                 }
@@ -293,18 +770,20 @@ impl State {
 }
 
 pub fn run(settings: &mut Settings) -> Result<(), AppError> {
-    let mut maybe_error = match settings.try_load_and_set_configuration() {
-        Ok(_) => None,
-        Err(ref error @ AppError::ConfigSyntax { ref source, .. }) => {
-            Some(format!("{}\n{}", error, source))
-        }
-        Err(error) => return Err(error),
-    };
-    settings.check_editor_command()?;
-    let theme = Theme::default();
+    let mut maybe_error = load_and_convert_errors(settings)?;
+    let mut sort_mode = settings.configuration.sssh.sort;
+    let mut collapsed_group_set = HashSet::new();
     loop {
         settings.ensure_script_file()?;
+        let history = History::try_load(settings.history_file.clone()).unwrap_or_else(|error| {
+            warn!(error = ?error, "Could not load history, servers are sorted without it");
+            History::default()
+        });
+        let theme = settings.configuration.theme;
         let mut state = State::try_from(settings.configuration.servers.clone())?;
+        state.collapsed_group_set = collapsed_group_set.clone();
+        state.has_inventory = !settings.configuration.sssh.inventory.command.is_empty();
+        state.set_sort_mode(sort_mode, &history);
         enable_raw_mode().map_err(|source| AppError::UI { source })?;
         let mut stdout = std::io::stdout();
         execute!(stdout, EnterAlternateScreen).map_err(|source| AppError::UI { source })?;
@@ -323,13 +802,15 @@ pub fn run(settings: &mut Settings) -> Result<(), AppError> {
         terminal
             .show_cursor()
             .map_err(|source| AppError::UI { source })?;
+        sort_mode = state.sort_mode;
+        collapsed_group_set = state.collapsed_group_set.clone();
         match result {
             Ok(ControlFlow::Edit) => {
                 maybe_error = edit_and_convert_errors(settings)?;
             }
             Ok(ControlFlow::Stop) => return Ok(()),
             Ok(ControlFlow::Selected) => {
-                let server = state.server_list[state.server_offset].clone();
+                let server = state.current_server().cloned().unwrap();
                 let username = server.username_list[state.username_offset].clone();
                 let run_scrip_result = if settings.skip_select {
                     println!(
@@ -343,41 +824,73 @@ pub fn run(settings: &mut Settings) -> Result<(), AppError> {
                 return run_scrip_result;
             }
             Ok(ControlFlow::Reload) => {
-                maybe_error = match settings.try_load_and_set_configuration() {
-                    Ok(_) => None,
-                    Err(ref error @ AppError::ConfigSyntax { ref source, .. }) => {
-                        Some(format!("{}\n{}", error, source))
-                    }
-                    Err(error) => return Err(error),
-                };
+                maybe_error = load_and_convert_errors(settings)?;
+            }
+            Ok(ControlFlow::RefreshInventory) => {
+                settings.refresh_inventory = true;
+                maybe_error = load_and_convert_errors(settings)?;
+                settings.refresh_inventory = false;
             }
             Err(error) => return Err(error),
         }
     }
 }
 
+fn load_and_convert_errors(settings: &mut Settings) -> Result<Option<String>, AppError> {
+    match settings.try_load_and_set_servers() {
+        Ok(_) => Ok(None),
+        Err(ref error) if error.is_configuration_error() => {
+            Ok(Some(error_to_string(error, String::new())))
+        }
+        Err(error) => Err(error),
+    }
+}
+
 fn edit_and_convert_errors(settings: &mut Settings) -> Result<Option<String>, AppError> {
+    let reload_key = settings.configuration.keymap.key_names(KeyAction::Reload);
     match edit(settings) {
         Ok(_) => Ok(None),
-        Err(ref error @ AppError::ConfigSyntax { .. }) => {
+        Err(ref error) if error.is_configuration_error() => {
             Ok(Some(error_to_string(error, String::new())))
         }
         Err(ref error @ AppError::EditorFastStop) => Ok(Some(format!(
-            "{}\nAfter editing configuration file press `r` to reload it.\n\nfile: {:?}",
+            "{}\nA copy of configuration file is edited, and it's checked and copied back only when the editor exits, so nothing is changed. Use an editor command that waits until you close the file (e.g. `code --wait`).\nPress `{}` to go back.\n\nfile: {:?}",
             error_to_string(error, String::new()),
+            reload_key,
             settings.configuration_file
         ))),
+        Err(ref error @ AppError::ConfigEditInvalid { .. }) => Ok(Some(format!(
+            "{}\nPress `{}` to go back.",
+            error_to_string(error, String::new()),
+            reload_key
+        ))),
+        Err(ref error @ AppError::CommandNotFound { .. }) => Ok(Some(format!(
+            "{}\nPress `{}` to go back and `{}`/`{}` to add/edit servers without an editor.",
+            error_to_string(error, String::new()),
+            reload_key,
+            settings
+                .configuration
+                .keymap
+                .key_names(KeyAction::AddServer),
+            settings
+                .configuration
+                .keymap
+                .key_names(KeyAction::EditServer),
+        ))),
         Err(ref error @ AppError::ProcessStart { .. }) => Ok(Some(format!(
-            "{}\nEdit the file manually and press `r` to reload it.",
-            error_to_string(error, String::new())
+            "{}\nEdit the file manually and press `{}` to reload it.",
+            error_to_string(error, String::new()),
+            reload_key
         ))),
         Err(ref error @ AppError::ProcessWait { .. }) => Ok(Some(format!(
-            "{}\nEdit the file manually and press `r` to reload it.",
-            error_to_string(error, String::new())
+            "{}\nEdit the file manually and press `{}` to reload it.",
+            error_to_string(error, String::new()),
+            reload_key
         ))),
         Err(ref error @ AppError::ProcessFailed { .. }) => Ok(Some(format!(
-            "{}\nEdit the file manually and press `r` to reload it.",
-            error_to_string(error, String::new())
+            "{}\nEdit the file manually and press `{}` to reload it.",
+            error_to_string(error, String::new()),
+            reload_key
         ))),
         Err(error) => Err(error),
     }
@@ -396,68 +909,126 @@ fn run_tui<B: Backend>(
     settings: &mut Settings,
     state: &mut State,
     terminal: &mut Terminal<B>,
-    maybe_error: Option<String>,
-    theme: Theme,
+    mut maybe_error: Option<String>,
+    mut theme: Theme,
 ) -> Result<ControlFlow, AppError> {
+    let mut watcher = FileWatcher::new(settings);
     loop {
         terminal
             .draw(|frame| draw_ui(settings, state, frame, maybe_error.clone(), theme))
             .map_err(|source| AppError::UI { source })?;
+        if !event::poll(FILE_WATCH_INTERVAL).map_err(|source| AppError::UI { source })? {
+            if watcher.is_changed(settings) {
+                maybe_error = reload_changed_configuration(settings, state);
+                theme = settings.configuration.theme;
+                // Included files may have changed too:
+                watcher.refresh(settings);
+            }
+            continue;
+        }
         let event = event::read().map_err(|source| AppError::UI { source })?;
         debug!(event = ?event, "Got new terminal event");
         let mut state_before_handling_event = state.clone();
+        let keymap = settings.configuration.keymap.clone();
         let maybe_control_flow = match event {
-            Key(key) => match key.code {
-                KeyCode::Char('q') => Some(ControlFlow::Stop),
-                KeyCode::Char('e') => Some(ControlFlow::Edit),
-                KeyCode::Char('r') => Some(ControlFlow::Reload),
-                KeyCode::Enter if state.choosing_username => Some(ControlFlow::Selected),
-                _ if maybe_error.is_none() => {
-                    match key.code {
-                        up_down if up_down == KeyCode::Up || up_down == KeyCode::Down => {
-                            if state.choosing_username {
-                                if up_down == KeyCode::Up {
-                                    state.previous_username()
-                                } else if up_down == KeyCode::Down {
-                                    state.next_username()
-                                } else {
-                                    unreachable!()
-                                }
-                            } else if up_down == KeyCode::Up {
-                                state.previous_server()
-                            } else if up_down == KeyCode::Down {
-                                state.next_server()
-                            } else {
-                                unreachable!()
+            // While the server form is open, every printable key belongs to its focused field:
+            Key(key) if state.form.is_some() => {
+                form_key_event(settings, state, &mut watcher, &keymap, key);
+                None
+            }
+            // While typing a filter, every printable key belongs to the filter:
+            Key(key) if state.filtering => {
+                match (key.code, keymap.action(&key)) {
+                    (KeyCode::Char(character), _)
+                        if !key
+                            .modifiers
+                            .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+                    {
+                        state.filter.push(character);
+                        state.apply_filter();
+                    }
+                    (KeyCode::Backspace, _) => {
+                        if state.filter.pop().is_none() {
+                            state.stop_filtering(true);
+                        } else {
+                            state.apply_filter();
+                        }
+                    }
+                    (KeyCode::Esc, _) => state.stop_filtering(true),
+                    (KeyCode::Enter, _) => state.stop_filtering(false),
+                    (_, Some(KeyAction::Up)) => state.previous_server(),
+                    (_, Some(KeyAction::Down)) => state.next_server(),
+                    (key_code, _) => debug!(key_code = ?key_code, "Unhandled terminal key event"),
+                };
+                None
+            }
+            Key(key) => match keymap.action(&key) {
+                Some(KeyAction::Quit) => Some(ControlFlow::Stop),
+                Some(KeyAction::Edit) => Some(ControlFlow::Edit),
+                Some(KeyAction::Reload) => Some(ControlFlow::Reload),
+                Some(KeyAction::RefreshInventory) if state.has_inventory => {
+                    Some(ControlFlow::RefreshInventory)
+                }
+                Some(KeyAction::Choose) if state.choosing_username => Some(ControlFlow::Selected),
+                Some(action) if maybe_error.is_none() => {
+                    match action {
+                        KeyAction::Filter if !state.all_server_list.is_empty() => {
+                            state.start_filtering()
+                        }
+                        KeyAction::ClearFilter if !state.filter.is_empty() => {
+                            state.stop_filtering(true)
+                        }
+                        KeyAction::Sort if !state.all_server_list.is_empty() => {
+                            state.next_sort_mode()
+                        }
+                        KeyAction::AddServer => {
+                            let maybe_group = state.current_group().or_else(|| {
+                                state
+                                    .current_server()
+                                    .map(|server| server.group.clone())
+                                    .filter(|group| !group.is_empty())
+                            });
+                            state.open_form(ServerForm::add(maybe_group))
+                        }
+                        _ if state.server_list.is_empty() => {
+                            debug!(action = ?action, "No server to choose")
+                        }
+                        KeyAction::Up if state.choosing_username => state.previous_username(),
+                        KeyAction::Down if state.choosing_username => state.next_username(),
+                        KeyAction::Up => state.previous_server(),
+                        KeyAction::Down => state.next_server(),
+                        KeyAction::Left if state.choosing_username => {
+                            state.choosing_username = false;
+                            state.username_list_state.select(None);
+                            state.choosing_server = true;
+                        }
+                        KeyAction::Left => state.collapse(),
+                        KeyAction::EditServer => {
+                            if let Some(server) = state.current_server().cloned() {
+                                state.open_form(ServerForm::edit(&server))
                             }
                         }
-                        left_right
-                            if [
-                                KeyCode::Left,
-                                KeyCode::Right,
-                                KeyCode::Backspace,
-                                KeyCode::Enter,
-                            ]
-                            .contains(&left_right) =>
-                        {
-                            if [KeyCode::Left, KeyCode::Backspace].contains(&left_right) {
-                                state.choosing_username = false;
-                                state.username_list_state.select(None);
-                                state.choosing_server = true;
-                            } else if [KeyCode::Right, KeyCode::Enter].contains(&left_right) {
-                                state.choosing_username = true;
-                                state.next_username();
-                                state.choosing_server = false;
+                        KeyAction::Right | KeyAction::Choose if state.current_group().is_some() => {
+                            let path = state.current_group().unwrap();
+                            if action == KeyAction::Choose
+                                && !state.collapsed_group_set.contains(&path)
+                            {
+                                state.collapse()
                             } else {
-                                unreachable!()
+                                state.expand(path)
                             }
                         }
-                        key_code => debug!(key_code = ?key_code, "Unhandled terminal key event"),
+                        KeyAction::Right | KeyAction::Choose => {
+                            state.choosing_username = true;
+                            state.next_username();
+                            state.choosing_server = false;
+                        }
+                        action => debug!(action = ?action, "Unhandled key action"),
                     };
                     None
                 }
-                key_code => {
-                    debug!(key_code = ?key_code, "Unhandled terminal key event");
+                _ => {
+                    debug!(key_code = ?key.code, "Unhandled terminal key event");
                     None
                 }
             },
@@ -476,6 +1047,69 @@ fn run_tui<B: Backend>(
     }
 }
 
+/// Reloads configuration after one of its files is changed on disk, without leaving the terminal
+/// UI. Returns the error to show instead of servers if the new configuration is broken.
+fn reload_changed_configuration(settings: &mut Settings, state: &mut State) -> Option<String> {
+    match settings.try_load_and_set_configuration() {
+        Ok(_) => {
+            state.has_inventory = !settings.configuration.sssh.inventory.command.is_empty();
+            state.set_servers(
+                settings.configuration.servers.clone(),
+                state.selected_row_id(),
+            );
+            state.show_notice("Configuration file changed and is reloaded.");
+            None
+        }
+        Err(error) => {
+            warn!(error = ?error, "Could not reload changed configuration");
+            Some(error_to_string(error, String::new()))
+        }
+    }
+}
+
+fn form_key_event(
+    settings: &mut Settings,
+    state: &mut State,
+    watcher: &mut FileWatcher,
+    keymap: &KeyMap,
+    key: KeyEvent,
+) {
+    let Some(form) = state.form.as_mut() else {
+        return;
+    };
+    match (key.code, keymap.action(&key)) {
+        (KeyCode::Char(character), _)
+            if !key
+                .modifiers
+                .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+        {
+            form.focused_value_mut().push(character);
+            form.error = None;
+        }
+        (KeyCode::Backspace, _) => {
+            form.focused_value_mut().pop();
+            form.error = None;
+        }
+        (KeyCode::Esc, _) => state.close_form(),
+        (KeyCode::Enter, _) => match form.try_save(settings) {
+            Ok(name) => {
+                watcher.refresh(settings);
+                state.set_servers(settings.configuration.servers.clone(), Some((false, name)));
+                state.close_form();
+            }
+            Err((maybe_field, reason)) => {
+                if let Some(field) = maybe_field {
+                    form.focus = field as usize;
+                }
+                form.error = Some(reason);
+            }
+        },
+        (KeyCode::Tab, _) | (_, Some(KeyAction::Down)) => form.next_field(),
+        (KeyCode::BackTab, _) | (_, Some(KeyAction::Up)) => form.previous_field(),
+        (key_code, _) => debug!(key_code = ?key_code, "Unhandled terminal key event"),
+    }
+}
+
 fn draw_ui<B: Backend>(
     settings: &mut Settings,
     state: &mut State,
@@ -487,7 +1121,10 @@ fn draw_ui<B: Backend>(
     let block = Block::default()
         .borders(Borders::ALL)
         .title(Span::styled(
-            crate_name!(),
+            match &settings.project_configuration_file {
+                Some(filename) => format!("{} (project: {})", crate_name!(), filename.display()),
+                None => crate_name!().to_string(),
+            },
             Style::default()
                 .bg(theme.main_border_title_bg)
                 .fg(theme.main_border_title_fg)
@@ -524,6 +1161,8 @@ fn draw_ui<B: Backend>(
     );
     if let Some(error) = maybe_error {
         error_ui(settings, state, frame, chunk_list[1], error, theme);
+    } else if state.form.is_some() {
+        server_form_ui(settings, state, frame, chunk_list[1], theme);
     } else {
         let table_chunk_list = Layout::default()
             .direction(Direction::Horizontal)
@@ -539,7 +1178,7 @@ fn draw_ui<B: Backend>(
 
 fn main_help_ui<B: Backend>(
     settings: &mut Settings,
-    _state: &mut State,
+    state: &mut State,
     frame: &mut Frame<B>,
     rect: Rect,
     maybe_error: Option<String>,
@@ -554,13 +1193,23 @@ fn main_help_ui<B: Backend>(
                 .add_modifier(Modifier::BOLD),
         ))
     } else {
-        let mut text_list = [Spans::from(Span::styled(
-            "Select Your SSH server to connect.",
-            Style::default()
-                .bg(theme.help_text_bg)
-                .fg(theme.help_text_fg)
-                .add_modifier(Modifier::BOLD),
-        ))]
+        // A notice replaces the first line for a moment since there may be no room for more:
+        let mut text_list = [Spans::from(match state.current_notice() {
+            Some(notice) => Span::styled(
+                notice.to_string(),
+                Style::default()
+                    .bg(theme.help_hint_text_bg)
+                    .fg(theme.help_hint_text_fg)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            None => Span::styled(
+                "Select Your SSH server to connect.",
+                Style::default()
+                    .bg(theme.help_text_bg)
+                    .fg(theme.help_text_fg)
+                    .add_modifier(Modifier::BOLD),
+            ),
+        })]
         .to_vec();
         if settings.is_default_servers() {
             text_list.push(Spans::from(
@@ -573,7 +1222,7 @@ fn main_help_ui<B: Backend>(
                             .add_modifier(Modifier::DIM),
                     ),
                     Span::styled(
-                        "e",
+                        settings.configuration.keymap.key_names(KeyAction::Edit),
                         Style::default()
                             .bg(theme.help_hint_text_bg)
                             .fg(theme.help_hint_text_fg)
@@ -622,7 +1271,12 @@ fn error_ui<B: Backend>(
                 max_line_length = line.len()
             };
             line_count += 1;
-            Spans::from(Span::styled(line, Style::default().fg(Color::LightRed)))
+            Spans::from(Span::styled(
+                line,
+                Style::default()
+                    .bg(theme.error_text_bg)
+                    .fg(theme.error_text_fg),
+            ))
         })
         .collect();
     let help_paragraph = Paragraph::new(error_text)
@@ -676,7 +1330,7 @@ fn error_ui<B: Backend>(
 }
 
 fn server_table_ui<B: Backend>(
-    _settings: &mut Settings,
+    settings: &mut Settings,
     state: &mut State,
     frame: &mut Frame<B>,
     rect: Rect,
@@ -700,60 +1354,144 @@ fn server_table_ui<B: Backend>(
         )
         .height(1)
         .bottom_margin(1);
-    let row_list = state.server_list.iter().map(|server| {
-        let height = server
-            .description
-            .clone()
-            .chars()
-            .filter(|character| *character == '\n')
-            .count()
-            + 1;
-        let cell_list = [
-            Cell::from(Span::styled(
-                server.name.clone(),
-                Style::default()
-                    .bg(theme.table_row_name_bg)
-                    .fg(theme.table_row_name_fg),
-            )),
-            Cell::from(Span::styled(
-                format!(
-                    "{}{}",
-                    server.hostname.clone(),
-                    if server.port == DEFAULT_PORT_NUMBER {
-                        String::new()
-                    } else {
-                        String::from(":") + server.port.to_string().as_str()
-                    }
-                ),
+    let match_style = Style::default()
+        .bg(theme.table_match_bg)
+        .fg(theme.table_match_fg)
+        .add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+    let row_list = state.row_list.iter().map(|row| {
+        let (offset, depth) = match row {
+            TableRow::Group {
+                path,
+                depth,
+                server_count,
+            } => {
+                let marker = if state.filter.is_empty() && state.collapsed_group_set.contains(path)
+                {
+                    "▸"
+                } else {
+                    "▾"
+                };
+                let name = path.rsplit('.').next().unwrap_or_default();
+                return Row::new(
+                    [Cell::from(Span::styled(
+                        format!(
+                            "{}{} {} ({})",
+                            "  ".repeat(*depth),
+                            marker,
+                            name,
+                            server_count
+                        ),
+                        Style::default()
+                            .bg(theme.table_row_name_bg)
+                            .fg(theme.table_row_name_fg)
+                            .add_modifier(Modifier::BOLD),
+                    ))]
+                    .to_vec(),
+                )
+                .height(1)
+                .bottom_margin(1);
+            }
+            TableRow::Server { offset, depth } => (*offset, *depth),
+        };
+        let (server, server_match) = (&state.server_list[offset], &state.server_match_list[offset]);
+        {
+            // With included files, also show where each server comes from:
+            let show_filename = !settings.configuration.include_list.is_empty();
+            let mut hostname_span_list = highlight(
+                &server.hostname,
+                0,
+                &server_match.hostname,
                 Style::default()
                     .bg(theme.table_row_hostname_bg)
                     .fg(theme.table_row_hostname_fg),
-            )),
-            Cell::from(
-                server
-                    .description
-                    .lines()
-                    .map(|line| {
-                        Spans::from(Span::styled(
-                            line,
-                            Style::default()
-                                .bg(theme.table_row_description_bg)
-                                .fg(theme.table_row_description_fg),
-                        ))
-                    })
-                    .collect::<Vec<_>>(),
-            ),
-        ]
-        .to_vec();
-        Row::new(cell_list).height(height as u16).bottom_margin(1)
+                match_style,
+            );
+            if server.port != DEFAULT_PORT_NUMBER {
+                hostname_span_list.push(Span::styled(
+                    String::from(":") + server.port.to_string().as_str(),
+                    Style::default()
+                        .bg(theme.table_row_hostname_bg)
+                        .fg(theme.table_row_hostname_fg),
+                ))
+            }
+            // Match offsets are for the whole description, so keep track of each line's offset:
+            let mut line_offset = 0;
+            let mut description_line_list = server
+                .description
+                .split('\n')
+                .map(|line| {
+                    let spans = Spans::from(highlight(
+                        line,
+                        line_offset,
+                        &server_match.description,
+                        Style::default()
+                            .bg(theme.table_row_description_bg)
+                            .fg(theme.table_row_description_fg),
+                        match_style,
+                    ));
+                    line_offset += line.chars().count() + 1;
+                    spans
+                })
+                .collect::<Vec<_>>();
+            if show_filename {
+                if server.description.is_empty() {
+                    description_line_list.clear()
+                }
+                description_line_list.push(Spans::from(Span::styled(
+                    format!("({})", server.filename.display()),
+                    Style::default()
+                        .bg(theme.table_row_filename_bg)
+                        .fg(theme.table_row_filename_fg),
+                )));
+            }
+            // Inside a group only the last part of the name is shown:
+            let group_length = if server.group.is_empty() {
+                0
+            } else {
+                server.group.len() + 1
+            };
+            let mut name_span_list = [Span::from("  ".repeat(depth))].to_vec();
+            name_span_list.append(&mut highlight(
+                &server.name[group_length..],
+                server.name[..group_length].chars().count(),
+                &server_match.name,
+                Style::default()
+                    .bg(theme.table_row_name_bg)
+                    .fg(theme.table_row_name_fg),
+                match_style,
+            ));
+            let height = description_line_list.len();
+            let cell_list = [
+                Cell::from(Spans::from(name_span_list)),
+                Cell::from(Spans::from(hostname_span_list)),
+                Cell::from(description_line_list),
+            ]
+            .to_vec();
+            Row::new(cell_list).height(height as u16).bottom_margin(1)
+        }
     });
+    let sort_title = match state.sort_mode {
+        SortMode::Name => "by name",
+        SortMode::Recent => "most recent",
+        SortMode::Frequent => "most frequent",
+    };
+    let title = if state.filtering || !state.filter.is_empty() {
+        format!(
+            "Servers ({}) [/{}{}]",
+            sort_title,
+            state.filter,
+            if state.filtering { "_" } else { "" }
+        )
+    } else {
+        format!("Servers ({})", sort_title)
+    };
     let table = Table::new(row_list)
         .header(table_header)
         .block(
             Block::default()
                 .borders(Borders::ALL)
                 .title(Span::styled(
-                    "Servers",
+                    title,
                     Style::default()
                         .bg(theme.table_border_title_bg)
                         .fg(theme.table_border_title_fg)
@@ -783,6 +1521,118 @@ fn server_table_ui<B: Backend>(
     frame.render_stateful_widget(table, rect, &mut state.server_table_state);
 }
 
+fn server_form_ui<B: Backend>(
+    _settings: &mut Settings,
+    state: &mut State,
+    frame: &mut Frame<B>,
+    rect: Rect,
+    theme: Theme,
+) {
+    let Some(form) = &state.form else {
+        return;
+    };
+    let title = match &form.original_name {
+        Some(name) => format!("Edit server {:?}", name),
+        None => "Add server".to_string(),
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(Span::styled(
+            title,
+            Style::default()
+                .bg(theme.table_border_title_bg)
+                .fg(theme.table_border_title_fg)
+                .add_modifier(Modifier::BOLD),
+        ))
+        .title_alignment(Alignment::Center)
+        .border_style(
+            Style::default()
+                .bg(theme.table_border_bg)
+                .fg(theme.table_border_active_fg),
+        );
+    let inner_rect = block.inner(rect);
+    frame.render_widget(block, rect);
+    let hint_style = Style::default()
+        .bg(theme.help_hint_text_bg)
+        .fg(theme.help_hint_text_fg)
+        .add_modifier(Modifier::DIM);
+    let label_width = FormField::ALL
+        .iter()
+        .map(|field| field.label().len())
+        .max()
+        .unwrap_or_default();
+    let mut line_list = Vec::new();
+    let mut cursor = (0, 0);
+    for (offset, field) in FormField::ALL.into_iter().enumerate() {
+        // Fields are single lines, so line breaks of descriptions are shown as symbols:
+        let value = form.value(field).replace('\n', "↵");
+        let label = format!("{:>width$}: ", field.label(), width = label_width);
+        if offset == form.focus {
+            cursor = (
+                label.chars().count() + value.chars().count(),
+                line_list.len(),
+            );
+        }
+        let hint = match field {
+            FormField::Name => "  Dotted names add the server to a group, e.g. prod.web1",
+            FormField::Port if value.is_empty() => "  Empty for the default port",
+            FormField::Users => "  Comma separated, empty for the default users",
+            _ => "",
+        };
+        line_list.push(Spans::from(vec![
+            Span::styled(
+                label,
+                Style::default()
+                    .bg(theme.table_title_bg)
+                    .fg(theme.table_title_fg)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::styled(
+                value,
+                if offset == form.focus {
+                    Style::default()
+                        .bg(theme.table_highlight_bg)
+                        .fg(theme.table_highlight_fg)
+                        .add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default()
+                        .bg(theme.table_row_name_bg)
+                        .fg(theme.table_row_name_fg)
+                },
+            ),
+            Span::styled(hint, hint_style),
+        ]));
+        line_list.push(Spans::default());
+    }
+    if let Some(error) = &form.error {
+        error.lines().for_each(|line| {
+            line_list.push(Spans::from(Span::styled(
+                line.to_string(),
+                Style::default()
+                    .bg(theme.error_text_bg)
+                    .fg(theme.error_text_fg)
+                    .add_modifier(Modifier::BOLD),
+            )))
+        });
+        line_list.push(Spans::default());
+    }
+    line_list.push(Spans::from(Span::styled(
+        "[Tab/Up/Down] Change field  [Enter] Save  [Esc] Cancel",
+        hint_style,
+    )));
+    let form_rect = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([Constraint::Min(0)].as_ref())
+        .split(inner_rect)[0];
+    let paragraph = Paragraph::new(line_list).alignment(Alignment::Left);
+    frame.render_widget(paragraph, form_rect);
+    let (column, row) = (cursor.0 as u16, cursor.1 as u16);
+    if column < form_rect.width && row < form_rect.height {
+        frame.set_cursor(form_rect.x + column, form_rect.y + row);
+    }
+}
+
 fn username_list_ui<B: Backend>(
     _settings: &mut Settings,
     state: &mut State,
@@ -790,9 +1640,11 @@ fn username_list_ui<B: Backend>(
     rect: Rect,
     theme: Theme,
 ) {
-    let server = state.server_list[state.server_offset].clone();
-    let items = server
-        .username_list
+    let username_list = state
+        .current_server()
+        .map(|server| server.username_list.clone())
+        .unwrap_or_default();
+    let items = username_list
         .iter()
         .map(|username| {
             ListItem::new(Span::styled(
@@ -834,8 +1686,10 @@ fn username_list_ui<B: Backend>(
     frame.render_stateful_widget(list, rect, &mut state.username_list_state);
 }
 
+// `usize::is_multiple_of` needs Rust 1.87:
+#[allow(clippy::manual_is_multiple_of)]
 fn help_key_ui<B: Backend>(
-    _settings: &mut Settings,
+    settings: &mut Settings,
     state: &mut State,
     frame: &mut Frame<B>,
     rect: Rect,
@@ -845,7 +1699,9 @@ fn help_key_ui<B: Backend>(
     // |----|----|----|----|
     // |----|----|----|----|
     // |----|----|----|----|
-    let key_info_list = state.working_keys.to_info_list();
+    let key_info_list = state
+        .working_keys
+        .to_info_list(&settings.configuration.keymap);
     let key_info_list_length = key_info_list.len();
     let column_size = 4;
     let mut row_size = key_info_list_length / column_size;
@@ -855,15 +1711,13 @@ fn help_key_ui<B: Backend>(
     let horizontal_constraint_list: Vec<Constraint> = (1..=row_size)
         .map(|_| Constraint::Percentage((100 / row_size) as u16))
         .collect();
-    let horizontal_constraint_list_lenght = horizontal_constraint_list.len();
     let horizontal_area_list = Layout::default()
         .direction(Direction::Vertical)
         // .margin(3)
         .constraints(horizontal_constraint_list)
         .split(rect);
     let mut rect_list = Vec::new();
-    for horizontal_constraint_offset in 0..horizontal_constraint_list_lenght {
-        let horizontal_area = horizontal_area_list[horizontal_constraint_offset];
+    for horizontal_area in horizontal_area_list {
         let horizontal_constraint_list: Vec<Constraint> = (1..=column_size)
             .map(|_| Constraint::Percentage((100 / column_size) as u16))
             .collect();
@@ -917,7 +1771,69 @@ fn help_key_ui<B: Backend>(
     }
 }
 
-fn run_script(
+/// Case-insensitive fuzzy matching of `pattern` against `text`.
+///
+/// Returns character offsets of `text` that matched. A contiguous match is preferred, otherwise
+/// characters of `pattern` only need to appear in `text` in the same order.
+fn fuzzy_match(pattern: &str, text: &str) -> Option<Vec<usize>> {
+    let pattern: Vec<_> = pattern.to_lowercase().chars().collect();
+    let text: Vec<_> = text.to_lowercase().chars().collect();
+    if pattern.is_empty() || pattern.len() > text.len() {
+        return None;
+    }
+    if let Some(start) = text
+        .windows(pattern.len())
+        .position(|window| window == pattern.as_slice())
+    {
+        return Some((start..start + pattern.len()).collect());
+    }
+    let mut offset_list = Vec::new();
+    let mut pattern_iter = pattern.iter().peekable();
+    for (offset, character) in text.iter().enumerate() {
+        if pattern_iter.peek() == Some(&character) {
+            offset_list.push(offset);
+            pattern_iter.next();
+        }
+    }
+    if pattern_iter.peek().is_none() {
+        Some(offset_list)
+    } else {
+        None
+    }
+}
+
+/// Splits `text` into spans where characters at `match_offset_list` (shifted by `start_offset`)
+/// are styled with `match_style`.
+fn highlight<'a>(
+    text: &'a str,
+    start_offset: usize,
+    match_offset_list: &[usize],
+    style: Style,
+    match_style: Style,
+) -> Vec<Span<'a>> {
+    let mut span_list = Vec::new();
+    let mut current = String::new();
+    let mut current_is_match = false;
+    for (offset, character) in text.chars().enumerate() {
+        let is_match = match_offset_list.contains(&(start_offset + offset));
+        if is_match != current_is_match && !current.is_empty() {
+            span_list.push(Span::styled(
+                current.clone(),
+                if current_is_match { match_style } else { style },
+            ));
+            current.clear();
+        }
+        current_is_match = is_match;
+        current.push(character);
+    }
+    span_list.push(Span::styled(
+        current,
+        if current_is_match { match_style } else { style },
+    ));
+    span_list
+}
+
+pub fn run_script(
     settings: &mut Settings,
     server: ConfigServer,
     username: String,
@@ -932,11 +1848,34 @@ fn run_script(
         port.clone(),
     ]
     .to_vec();
+    // Optional fields are always set (empty if they are not configured):
+    let forward_agent = match server.forward_agent {
+        Some(true) => "yes",
+        Some(false) => "no",
+        None => "",
+    };
+    // One `<KEY>=<VALUE>` per line, so each line can be passed to `ssh -o`:
+    let ssh_options = server
+        .ssh_options
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join("\n");
     let mut env_list = [
         ("SSSH_ADDRESS", address.as_str()),
         ("SSSH_USERNAME", username.as_str()),
         ("SSSH_HOSTNAME", hostname.as_str()),
         ("SSSH_PORT", port.as_str()),
+        (
+            "SSSH_IDENTITY_FILE",
+            server.identity_file.as_deref().unwrap_or_default(),
+        ),
+        (
+            "SSSH_PROXY_JUMP",
+            server.proxy_jump.as_deref().unwrap_or_default(),
+        ),
+        ("SSSH_FORWARD_AGENT", forward_agent),
+        ("SSSH_SSH_OPTIONS", ssh_options.as_str()),
     ]
     .to_vec();
     if settings.verbose {
@@ -949,6 +1888,10 @@ fn run_script(
         .into_iter()
         .map(PathBuf::from)
         .collect::<Vec<_>>();
+    if let Err(error) = History::try_append(settings.history_file.clone(), &server.name, &username)
+    {
+        warn!(error = ?error, "Could not record connection in history file");
+    }
     let command = settings.script_file.clone();
     let _ = run_command("Script", command, argument_list, env_list)?;
     Ok(())
@@ -3,23 +3,52 @@
 # `sssh` runs this script with the following arguments:
 #    /path/to/this/script "<USERNAME>@<HOSTNAME>" "<USERNAME>" "<HOSTNAME>" "<PORT>" "<DEBUG>"
 # It also sets the following environment variables:
-#    SSSH_ADDRESS  = "<USERNAME>@<HOSTNAME>"
-#    SSSH_USERNAME = "<USERNAME>"
-#    SSSH_HOSTNAME = "<HOSTNAME>"
-#    SSSH_PORT     = "<PORT>"
-#    SSSH_DEBUG    = "<DEBUG>"
+#    SSSH_ADDRESS       = "<USERNAME>@<HOSTNAME>"
+#    SSSH_USERNAME      = "<USERNAME>"
+#    SSSH_HOSTNAME      = "<HOSTNAME>"
+#    SSSH_PORT          = "<PORT>"
+#    SSSH_IDENTITY_FILE = "<IDENTITY_FILE>"  # "" if server has no `identity_file`
+#    SSSH_PROXY_JUMP    = "<PROXY_JUMP>"     # "" if server has no `proxy_jump`
+#    SSSH_FORWARD_AGENT = "<yes|no>"         # "" if server has no `forward_agent`
+#    SSSH_SSH_OPTIONS   = "<KEY>=<VALUE>"    # One line for each of `ssh_options` of server
+#    SSSH_DEBUG         = "<DEBUG>"
 # If `sssh` itself is started with --verbose (or -v), <DEBUG> will be "1", otherwise "0"
 
 set -e
 if [ "${SSSH_DEBUG}" = "1" ]; then
   # `sssh` forwards its own logging messages to `stderr`.
   # We recommend you to do the same here.
-  echo "Arguments:     " "$@"               >&2
-  echo "SSSH_ADDRESS:  " "${SSSH_ADDRESS}"  >&2
-  echo "SSSH_USERNAME: " "${SSSH_USERNAME}" >&2
-  echo "SSSH_HOSTNAME: " "${SSSH_HOSTNAME}" >&2
-  echo "SSSH_PORT:     " "${SSSH_PORT}"     >&2
+  echo "Arguments:          " "$@"                    >&2
+  echo "SSSH_ADDRESS:       " "${SSSH_ADDRESS}"       >&2
+  echo "SSSH_USERNAME:      " "${SSSH_USERNAME}"      >&2
+  echo "SSSH_HOSTNAME:      " "${SSSH_HOSTNAME}"      >&2
+  echo "SSSH_PORT:          " "${SSSH_PORT}"          >&2
+  echo "SSSH_IDENTITY_FILE: " "${SSSH_IDENTITY_FILE}" >&2
+  echo "SSSH_PROXY_JUMP:    " "${SSSH_PROXY_JUMP}"    >&2
+  echo "SSSH_FORWARD_AGENT: " "${SSSH_FORWARD_AGENT}" >&2
+  echo "SSSH_SSH_OPTIONS:   " "${SSSH_SSH_OPTIONS}"   >&2
   set -xe
 fi
 
-ssh -p "${SSSH_PORT}" "${SSSH_ADDRESS}"
+# Build `ssh` flags from optional server fields:
+set -- -p "${SSSH_PORT}"
+if [ -n "${SSSH_IDENTITY_FILE}" ]; then
+  set -- "$@" -i "${SSSH_IDENTITY_FILE}"
+fi
+if [ -n "${SSSH_PROXY_JUMP}" ]; then
+  set -- "$@" -J "${SSSH_PROXY_JUMP}"
+fi
+if [ "${SSSH_FORWARD_AGENT}" = "yes" ]; then
+  set -- "$@" -A
+elif [ "${SSSH_FORWARD_AGENT}" = "no" ]; then
+  set -- "$@" -a
+fi
+if [ -n "${SSSH_SSH_OPTIONS}" ]; then
+  while IFS= read -r option; do
+    set -- "$@" -o "${option}"
+  done <<EOF
+${SSSH_SSH_OPTIONS}
+EOF
+fi
+
+ssh "$@" "${SSSH_ADDRESS}"
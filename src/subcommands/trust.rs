@@ -0,0 +1,35 @@
+use crate::error::AppError;
+use crate::settings::{find_project_configuration_file, Settings, SubCommand};
+use crate::trust;
+use anyhow::Result;
+use std::env;
+
+pub fn run(settings: &mut Settings) -> Result<(), AppError> {
+    let revoke = if let SubCommand::Trust { revoke } = settings.subcommand {
+        revoke
+    } else {
+        // It's already checked in main.rs
+        unreachable!()
+    };
+    let filename =
+        find_project_configuration_file().ok_or_else(|| AppError::ProjectConfigNotFound {
+            directory: env::current_dir().unwrap_or_default(),
+        })?;
+    if revoke {
+        if trust::try_revoke(&settings.trust_directory, &filename)? {
+            println!(
+                "Project configuration file {:?} is not trusted anymore.",
+                filename
+            );
+        } else {
+            println!("Project configuration file {:?} was not trusted.", filename);
+        }
+    } else {
+        trust::try_trust(&settings.trust_directory, &filename)?;
+        println!(
+            "Trusted project configuration file {:?} until it changes. Run `sssh trust` again after you review its changes.",
+            filename
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,343 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use tui::style::Color;
+
+/// Color theme from the `[sssh.theme]` table (or `--theme-file`).
+///
+/// `preset` selects a built-in theme and every other key overrides one color of it, e.g.
+/// `table_border_fg = "#ff8800"`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ConfigTheme {
+    pub preset: Option<String>,
+    #[serde(flatten)]
+    pub color_map: HashMap<String, toml::Value>,
+}
+
+impl ConfigTheme {
+    /// Values of `other` take precedence.
+    pub fn merge(&self, other: &Self) -> Self {
+        let mut color_map = self.color_map.clone();
+        color_map.extend(other.color_map.clone());
+        Self {
+            preset: other.preset.clone().or_else(|| self.preset.clone()),
+            color_map,
+        }
+    }
+}
+
+macro_rules! theme {
+    ($($field:ident),* $(,)?) => {
+        #[derive(Debug, Clone, PartialEq, Copy)]
+        pub struct Theme {
+            $(pub $field: Color,)*
+        }
+
+        impl Theme {
+            fn set_color(&mut self, name: &str, color: Color) -> Result<(), String> {
+                match name {
+                    $(stringify!($field) => self.$field = color,)*
+                    _ => return Err(format!("unknown theme color {:?}", name)),
+                };
+                Ok(())
+            }
+
+            fn monochrome() -> Self {
+                Self {
+                    $($field: Color::Reset,)*
+                }
+            }
+        }
+    };
+}
+
+theme!(
+    main_border_bg,
+    main_border_fg,
+    main_border_title_bg,
+    main_border_title_fg,
+    help_text_bg,
+    help_text_fg,
+    help_hint_text_bg,
+    help_hint_text_fg,
+    help_error_text_bg,
+    help_error_text_fg,
+    help_paragraph_bg,
+    help_paragraph_fg,
+    error_text_bg,
+    error_text_fg,
+    table_border_bg,
+    table_border_fg,
+    table_border_active_fg,
+    table_border_title_bg,
+    table_border_title_fg,
+    table_title_bg,
+    table_title_fg,
+    table_row_name_bg,
+    table_row_name_fg,
+    table_row_hostname_bg,
+    table_row_hostname_fg,
+    table_row_description_bg,
+    table_row_description_fg,
+    table_row_filename_bg,
+    table_row_filename_fg,
+    table_highlight_bg,
+    table_highlight_fg,
+    table_match_bg,
+    table_match_fg,
+    list_border_bg,
+    list_border_fg,
+    list_border_active_fg,
+    list_border_title_bg,
+    list_border_title_fg,
+    username_bg,
+    username_fg,
+    list_highlight_bg,
+    list_highlight_fg,
+    help_key_working_bg,
+    help_key_working_fg,
+    help_key_not_working_bg,
+    help_key_not_working_fg,
+    help_key_guide_working_bg,
+    help_key_guide_working_fg,
+    help_key_guide_not_working_bg,
+    help_key_guide_not_working_fg,
+);
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+impl TryFrom<&ConfigTheme> for Theme {
+    type Error = String;
+
+    fn try_from(config_theme: &ConfigTheme) -> Result<Self, Self::Error> {
+        let mut theme = match config_theme.preset.as_deref() {
+            None | Some("dark") => Self::dark(),
+            Some("light") => Self::light(),
+            Some("high-contrast") => Self::high_contrast(),
+            Some("monochrome") => Self::monochrome(),
+            Some(preset) => {
+                return Err(format!(
+                    "unknown theme preset {:?} (available presets: dark, light, high-contrast, monochrome)",
+                    preset
+                ))
+            }
+        };
+        let mut name_list: Vec<_> = config_theme.color_map.keys().collect();
+        name_list.sort();
+        for name in name_list {
+            let color = parse_color(&config_theme.color_map[name])
+                .map_err(|reason| format!("{} for theme color {:?}", reason, name))?;
+            theme.set_color(name, color)?;
+        }
+        // https://no-color.org
+        if env::var("NO_COLOR").is_ok_and(|value| !value.is_empty()) {
+            theme = Self::monochrome()
+        }
+        Ok(theme)
+    }
+}
+
+impl Theme {
+    fn dark() -> Self {
+        Self {
+            main_border_bg: Color::Reset,
+            main_border_fg: Color::White,
+            main_border_title_bg: Color::Reset,
+            main_border_title_fg: Color::White,
+
+            help_text_bg: Color::Reset,
+            help_text_fg: Color::White,
+            help_hint_text_bg: Color::Reset,
+            help_hint_text_fg: Color::White,
+            help_error_text_bg: Color::Reset,
+            help_error_text_fg: Color::Red,
+            help_paragraph_bg: Color::Reset,
+            help_paragraph_fg: Color::Reset,
+            error_text_bg: Color::Reset,
+            error_text_fg: Color::LightRed,
+
+            table_border_bg: Color::Reset,
+            table_border_fg: Color::Yellow,
+            table_border_active_fg: Color::LightYellow,
+            table_border_title_bg: Color::Reset,
+            table_border_title_fg: Color::Yellow,
+            table_title_bg: Color::Reset,
+            table_title_fg: Color::LightYellow,
+            table_row_name_bg: Color::Reset,
+            table_row_name_fg: Color::White,
+            table_row_hostname_bg: Color::Reset,
+            table_row_hostname_fg: Color::Green,
+            table_row_description_bg: Color::Reset,
+            table_row_description_fg: Color::LightBlue,
+            table_row_filename_bg: Color::Reset,
+            table_row_filename_fg: Color::DarkGray,
+            table_highlight_bg: Color::Reset,
+            table_highlight_fg: Color::Reset,
+            table_match_bg: Color::Reset,
+            table_match_fg: Color::LightMagenta,
+
+            list_border_bg: Color::Reset,
+            list_border_fg: Color::Yellow,
+            list_border_active_fg: Color::LightYellow,
+            list_border_title_bg: Color::Reset,
+            list_border_title_fg: Color::Yellow,
+            username_bg: Color::Reset,
+            username_fg: Color::LightBlue,
+            list_highlight_bg: Color::Reset,
+            list_highlight_fg: Color::Reset,
+
+            help_key_working_bg: Color::Reset,
+            help_key_working_fg: Color::LightYellow,
+            help_key_not_working_bg: Color::Reset,
+            help_key_not_working_fg: Color::DarkGray,
+            help_key_guide_working_bg: Color::Reset,
+            help_key_guide_working_fg: Color::White,
+            help_key_guide_not_working_bg: Color::Reset,
+            help_key_guide_not_working_fg: Color::DarkGray,
+        }
+    }
+
+    fn light() -> Self {
+        Self {
+            main_border_fg: Color::Black,
+            main_border_title_fg: Color::Black,
+
+            help_text_fg: Color::Black,
+            help_hint_text_fg: Color::Black,
+            error_text_fg: Color::Red,
+
+            table_border_fg: Color::Blue,
+            table_border_active_fg: Color::Magenta,
+            table_border_title_fg: Color::Blue,
+            table_title_fg: Color::Magenta,
+            table_row_name_fg: Color::Black,
+            table_row_description_fg: Color::Blue,
+            table_row_filename_fg: Color::Gray,
+            table_match_fg: Color::Red,
+
+            list_border_fg: Color::Blue,
+            list_border_active_fg: Color::Magenta,
+            list_border_title_fg: Color::Blue,
+            username_fg: Color::Blue,
+
+            help_key_working_fg: Color::Magenta,
+            help_key_not_working_fg: Color::Gray,
+            help_key_guide_working_fg: Color::Black,
+            help_key_guide_not_working_fg: Color::Gray,
+            ..Self::dark()
+        }
+    }
+
+    fn high_contrast() -> Self {
+        let mut theme = Self {
+            main_border_fg: Color::White,
+            main_border_title_fg: Color::White,
+
+            help_text_fg: Color::White,
+            help_hint_text_fg: Color::White,
+            help_error_text_fg: Color::LightRed,
+            help_paragraph_fg: Color::White,
+            error_text_fg: Color::LightRed,
+
+            table_border_fg: Color::White,
+            table_border_active_fg: Color::LightYellow,
+            table_border_title_fg: Color::White,
+            table_title_fg: Color::LightYellow,
+            table_row_name_fg: Color::White,
+            table_row_hostname_fg: Color::LightGreen,
+            table_row_description_fg: Color::LightCyan,
+            table_row_filename_fg: Color::Gray,
+            table_highlight_bg: Color::White,
+            table_highlight_fg: Color::Black,
+            table_match_fg: Color::LightMagenta,
+
+            list_border_fg: Color::White,
+            list_border_active_fg: Color::LightYellow,
+            list_border_title_fg: Color::White,
+            username_fg: Color::LightCyan,
+            list_highlight_bg: Color::White,
+            list_highlight_fg: Color::Black,
+
+            help_key_working_fg: Color::LightYellow,
+            help_key_not_working_fg: Color::Gray,
+            help_key_guide_working_fg: Color::White,
+            help_key_guide_not_working_fg: Color::Gray,
+            ..Self::dark()
+        };
+        // Everything else is drawn on black:
+        [
+            &mut theme.main_border_bg,
+            &mut theme.main_border_title_bg,
+            &mut theme.help_text_bg,
+            &mut theme.help_hint_text_bg,
+            &mut theme.help_error_text_bg,
+            &mut theme.help_paragraph_bg,
+            &mut theme.error_text_bg,
+            &mut theme.table_border_bg,
+            &mut theme.table_border_title_bg,
+            &mut theme.table_title_bg,
+            &mut theme.list_border_bg,
+            &mut theme.list_border_title_bg,
+            &mut theme.help_key_working_bg,
+            &mut theme.help_key_not_working_bg,
+            &mut theme.help_key_guide_working_bg,
+            &mut theme.help_key_guide_not_working_bg,
+        ]
+        .into_iter()
+        .for_each(|color| *color = Color::Black);
+        theme
+    }
+}
+
+/// Parses a named color (`"light-blue"`), a 256-color index (`208`) or a hex color (`"#ff8800"`).
+fn parse_color(value: &toml::Value) -> Result<Color, String> {
+    let color = match value {
+        toml::Value::Integer(index) => {
+            return u8::try_from(*index)
+                .map(Color::Indexed)
+                .map_err(|_| format!("invalid 256-color index {}", index))
+        }
+        toml::Value::String(color) => color.trim(),
+        value => return Err(format!("invalid color {}", value)),
+    };
+    if let Some(hex) = color.strip_prefix('#') {
+        let invalid_hex = || format!("invalid hex color {:?}", color);
+        // Checked before slicing, so non-ASCII characters can't split a byte range:
+        if hex.len() != 6 || !hex.chars().all(|character| character.is_ascii_hexdigit()) {
+            return Err(invalid_hex());
+        }
+        let channel = |offset: usize| {
+            u8::from_str_radix(&hex[offset..offset + 2], 16).map_err(|_| invalid_hex())
+        };
+        return Ok(Color::Rgb(channel(0)?, channel(2)?, channel(4)?));
+    }
+    if let Ok(index) = color.parse::<u8>() {
+        return Ok(Color::Indexed(index));
+    }
+    Ok(
+        match color.to_lowercase().replace(['-', '_', ' '], "").as_str() {
+            "reset" | "default" => Color::Reset,
+            "black" => Color::Black,
+            "red" => Color::Red,
+            "green" => Color::Green,
+            "yellow" => Color::Yellow,
+            "blue" => Color::Blue,
+            "magenta" => Color::Magenta,
+            "cyan" => Color::Cyan,
+            "gray" | "grey" => Color::Gray,
+            "darkgray" | "darkgrey" => Color::DarkGray,
+            "lightred" => Color::LightRed,
+            "lightgreen" => Color::LightGreen,
+            "lightyellow" => Color::LightYellow,
+            "lightblue" => Color::LightBlue,
+            "lightmagenta" => Color::LightMagenta,
+            "lightcyan" => Color::LightCyan,
+            "white" => Color::White,
+            _ => return Err(format!("unknown color {:?}", color)),
+        },
+    )
+}
@@ -0,0 +1,78 @@
+use crate::error::AppError;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info};
+
+/// Whether the user trusted current contents of project-local configuration file `filename`
+/// with `sssh trust` (like `direnv allow`).
+///
+/// A copy of each trusted file is kept in `directory`, so a file that changes after it's trusted
+/// (e.g. by `git pull`) is not trusted anymore.
+pub fn is_trusted(directory: &Path, filename: &Path, configuration: &str) -> bool {
+    fs::read_to_string(copy_filename(directory, filename))
+        .ok()
+        .and_then(|copy| {
+            copy.split_once('\n')
+                .map(|(path, copy)| path == key(filename) && copy == configuration)
+        })
+        .unwrap_or_default()
+}
+
+/// Trusts current contents of `filename`.
+pub fn try_trust(directory: &Path, filename: &Path) -> Result<(), AppError> {
+    let configuration = fs::read_to_string(filename).map_err(|error| AppError::FileRead {
+        title: "project configuration",
+        filename: filename.to_path_buf(),
+        source: error,
+    })?;
+    let copy_filename = copy_filename(directory, filename);
+    fs::create_dir_all(directory)
+        .and_then(|_| {
+            // The first line is the path of file, since copies are named after its hash:
+            fs::write(
+                copy_filename.clone(),
+                format!("{}\n{}", key(filename), configuration),
+            )
+        })
+        .map_err(|error| AppError::FileWrite {
+            title: "trusted project configuration",
+            filename: copy_filename.clone(),
+            source: error,
+        })?;
+    info!(filename = ?filename, copy_filename = ?copy_filename, "Trusted project configuration file");
+    Ok(())
+}
+
+/// Stops trusting `filename` and returns whether it was trusted.
+pub fn try_revoke(directory: &Path, filename: &Path) -> Result<bool, AppError> {
+    let copy_filename = copy_filename(directory, filename);
+    if !copy_filename.exists() {
+        debug!(filename = ?filename, "Project configuration file is not trusted");
+        return Ok(false);
+    }
+    fs::remove_file(copy_filename.clone()).map_err(|error| AppError::FileWrite {
+        title: "trusted project configuration",
+        filename: copy_filename,
+        source: error,
+    })?;
+    info!(filename = ?filename, "Revoked trust of project configuration file");
+    Ok(true)
+}
+
+fn copy_filename(directory: &Path, filename: &Path) -> PathBuf {
+    // FNV-1a, only to have a short and stable name:
+    let hash = key(filename)
+        .bytes()
+        .fold(0xcbf29ce484222325_u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        });
+    directory.join(format!("{:016x}.toml", hash))
+}
+
+fn key(filename: &Path) -> String {
+    filename
+        .canonicalize()
+        .unwrap_or_else(|_| filename.to_path_buf())
+        .to_string_lossy()
+        .replace('\n', " ")
+}
@@ -48,9 +48,9 @@ pub fn run_command(
             "{} process failed", title
         );
         let error = if output.trim().is_empty() {
-            io::Error::new(io::ErrorKind::Other, format!("{} process failed", title))
+            io::Error::other(format!("{} process failed", title))
         } else {
-            io::Error::new(io::ErrorKind::Other, output)
+            io::Error::other(output)
         };
         return Err(AppError::ProcessFailed {
             command,
@@ -0,0 +1,507 @@
+use crate::error::AppError;
+use crate::settings::{Config, ConfigServer};
+use serde::de::{DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// Keys of `[sssh]` table.
+const APP_KEY_LIST: &[&str] = &[
+    "include",
+    "sort",
+    "keys",
+    "theme",
+    "inventory",
+    "templates",
+    "defaults",
+    "match",
+    "backup_count",
+    "interpolate",
+];
+/// Keys of server tables (including aliases).
+pub const SERVER_KEY_LIST: &[&str] = &[
+    "group",
+    "users",
+    "username_list",
+    "hostname",
+    "port",
+    "description",
+    "identity_file",
+    "proxy_jump",
+    "ssh_options",
+    "forward_agent",
+    "tags",
+    "tag_list",
+    "extends",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Error => write!(formatter, "error"),
+            Self::Warning => write!(formatter, "warning"),
+        }
+    }
+}
+
+/// A problem of configuration and where it is. Line and column start from 1.
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostic {
+    pub file: PathBuf,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "{}", self.file.display())?;
+        if let (Some(line), Some(column)) = (self.line, self.column) {
+            write!(formatter, ":{}:{}", line, column)?;
+        }
+        write!(formatter, ": {}: {}", self.severity, self.message)
+    }
+}
+
+/// Contents of configuration files by their names, to find where problems are.
+struct SourceMap(HashMap<PathBuf, String>);
+
+impl SourceMap {
+    /// A diagnostic at the value of `path` (or the closest table that contains it) in `file`.
+    fn diagnostic(
+        &self,
+        file: &Path,
+        path: &[String],
+        severity: Severity,
+        message: String,
+    ) -> Diagnostic {
+        // Included files are only read while loading, so a file may have to be read again:
+        let maybe_source = self
+            .0
+            .get(file)
+            .cloned()
+            .or_else(|| std::fs::read_to_string(file).ok());
+        let maybe_position = maybe_source
+            .and_then(|source| locate(&source, path).map(|span| position(&source, span.start)));
+        Diagnostic {
+            file: file.to_path_buf(),
+            line: maybe_position.map(|(line, _)| line),
+            column: maybe_position.map(|(_, column)| column),
+            severity,
+            message,
+        }
+    }
+}
+
+/// Validates configuration layers (lowest precedence first) and files that they include. It
+/// returns every problem that it finds, and the configuration if it could be loaded at all.
+///
+/// Besides the errors that prevent loading, it finds keys that sssh doesn't know (which are
+/// otherwise ignored), servers with an empty hostname, port 0 or an empty username, and servers
+/// that share the same hostname and port.
+pub fn validate(
+    layer_list: Vec<(PathBuf, String)>,
+    filename: &Path,
+) -> (Option<Config>, Vec<Diagnostic>) {
+    let mut source_map = SourceMap(layer_list.iter().cloned().collect());
+    let mut diagnostic_list = Vec::new();
+    let mut table_list = Vec::new();
+    for (layer_filename, layer) in layer_list.iter() {
+        match toml::from_str::<toml::Table>(layer) {
+            Ok(table) => table_list.push((layer_filename.clone(), table)),
+            Err(error) => diagnostic_list.push(syntax_diagnostic(layer_filename, layer, &error)),
+        }
+    }
+    if !diagnostic_list.is_empty() {
+        return (None, diagnostic_list);
+    }
+    let maybe_config = match Config::parse_layers(layer_list, filename) {
+        Ok(config) => Some(config),
+        Err(error_list) => {
+            diagnostic_list.extend(
+                error_list
+                    .into_iter()
+                    .map(|error| error_diagnostic(&source_map, error)),
+            );
+            None
+        }
+    };
+    for (include_filename, include) in maybe_config
+        .iter()
+        .flat_map(|config| config.include_list.iter())
+    {
+        source_map
+            .0
+            .insert(include_filename.clone(), include.clone());
+        if let Ok(table) = toml::from_str::<toml::Table>(include) {
+            table_list.push((include_filename.clone(), table));
+        }
+    }
+    for (table_filename, table) in table_list.iter() {
+        check_tables(&source_map, table_filename, table, &mut diagnostic_list);
+    }
+    if let Some(config) = &maybe_config {
+        check_servers(&source_map, config, &mut diagnostic_list);
+    }
+    diagnostic_list
+        .sort_by_key(|diagnostic| (diagnostic.file.clone(), diagnostic.line, diagnostic.column));
+    (maybe_config, diagnostic_list)
+}
+
+fn syntax_diagnostic(filename: &Path, source: &str, error: &toml::de::Error) -> Diagnostic {
+    let maybe_position = error.span().map(|span| position(source, span.start));
+    Diagnostic {
+        file: filename.to_path_buf(),
+        line: maybe_position.map(|(line, _)| line),
+        column: maybe_position.map(|(_, column)| column),
+        severity: Severity::Error,
+        message: error.message().to_string(),
+    }
+}
+
+fn error_diagnostic(source_map: &SourceMap, error: AppError) -> Diagnostic {
+    let (filename, path) = match &error {
+        AppError::ConfigSyntax { filename, source } => {
+            if let Some(layer) = source_map.0.get(filename) {
+                return syntax_diagnostic(filename, layer, source);
+            }
+            (filename.clone(), Vec::new())
+        }
+        AppError::ConfigEntry {
+            filename,
+            name,
+            reason,
+        } => {
+            // Type errors end with the key that has the wrong type, e.g. "... in `port`":
+            let mut path = split_name(name);
+            if let Some(key) = reason
+                .rsplit_once(" in `")
+                .and_then(|(_, key)| key.strip_suffix('`'))
+            {
+                path.extend(split_name(key));
+            }
+            (filename.clone(), path)
+        }
+        AppError::ConfigVariable { filename, name, .. }
+        | AppError::ConfigTemplateNotFound { filename, name, .. } => {
+            (filename.clone(), split_name(name))
+        }
+        AppError::ConfigDuplicateServer {
+            other_filename,
+            name,
+            ..
+        } => (other_filename.clone(), split_name(name)),
+        AppError::ConfigTemplateCycle { filename, chain } => (
+            filename.clone(),
+            chain
+                .first()
+                .map(|name| split_name(name))
+                .unwrap_or_default(),
+        ),
+        AppError::ConfigInclude { filename, .. } => (filename.clone(), split_name("sssh.include")),
+        AppError::ConfigKeyBinding { filename, .. } => (filename.clone(), split_name("sssh.keys")),
+        AppError::ConfigTheme { filename, .. } => (filename.clone(), split_name("sssh.theme")),
+        _ => (PathBuf::new(), Vec::new()),
+    };
+    source_map.diagnostic(&filename, &path, Severity::Error, error.to_string())
+}
+
+/// `sssh.match[0].set` is `["sssh", "match", "0", "set"]`.
+fn split_name(name: &str) -> Vec<String> {
+    name.replace('[', ".")
+        .replace(']', "")
+        .split('.')
+        .map(|key| key.to_string())
+        .collect()
+}
+
+/// Finds unknown keys and empty `users` of server tables, templates and rules.
+fn check_tables(
+    source_map: &SourceMap,
+    filename: &Path,
+    table: &toml::Table,
+    diagnostic_list: &mut Vec<Diagnostic>,
+) {
+    let unknown_key = |path: Vec<String>, description: &str| {
+        let message = format!(
+            "unknown key `{}` of {}",
+            path.last().cloned().unwrap_or_default(),
+            description
+        );
+        source_map.diagnostic(filename, &path, Severity::Error, message)
+    };
+    let mut server_table_list = Vec::new();
+    if let Some(app_table) = table.get("sssh").and_then(|value| value.as_table()) {
+        for key in app_table.keys() {
+            if !APP_KEY_LIST.contains(&key.as_str()) {
+                diagnostic_list.push(unknown_key(vec!["sssh".to_string(), key.clone()], "[sssh]"));
+            }
+        }
+        if let Some(template_table) = app_table
+            .get("templates")
+            .and_then(|value| value.as_table())
+        {
+            for (name, template) in template_table.iter() {
+                if let Some(template) = template.as_table() {
+                    server_table_list.push((
+                        vec!["sssh".to_string(), "templates".to_string(), name.clone()],
+                        template,
+                    ));
+                }
+            }
+        }
+        if let Some(defaults) = app_table.get("defaults").and_then(|value| value.as_table()) {
+            let mut defaults = defaults.clone();
+            defaults.remove("local_user");
+            for key in defaults.keys() {
+                if !SERVER_KEY_LIST.contains(&key.as_str()) {
+                    diagnostic_list.push(unknown_key(
+                        vec!["sssh".to_string(), "defaults".to_string(), key.clone()],
+                        "[sssh.defaults]",
+                    ));
+                }
+            }
+        }
+        if let Some(match_list) = app_table.get("match").and_then(|value| value.as_array()) {
+            for (index, rule) in match_list.iter().enumerate() {
+                if let Some(set) = rule.get("set").and_then(|value| value.as_table()) {
+                    server_table_list.push((
+                        vec![
+                            "sssh".to_string(),
+                            "match".to_string(),
+                            index.to_string(),
+                            "set".to_string(),
+                        ],
+                        set,
+                    ));
+                }
+            }
+        }
+    }
+    collect_server_tables(table, Vec::new(), &mut server_table_list);
+    for (path, server_table) in server_table_list {
+        for (key, value) in server_table.iter() {
+            let mut key_path = path.clone();
+            key_path.push(key.clone());
+            if !SERVER_KEY_LIST.contains(&key.as_str()) {
+                diagnostic_list.push(unknown_key(
+                    key_path,
+                    &format!("server {:?}", path.join(".")),
+                ));
+            } else if ["users", "username_list"].contains(&key.as_str())
+                && value.as_array().is_some_and(|array| array.is_empty())
+            {
+                diagnostic_list.push(source_map.diagnostic(
+                    filename,
+                    &key_path,
+                    Severity::Error,
+                    format!("server {:?} has an empty list of users", path.join(".")),
+                ));
+            }
+        }
+    }
+}
+
+/// Same as how servers are found while loading: tables with `hostname` or `extends` are servers
+/// and other tables are groups.
+fn collect_server_tables<'a>(
+    table: &'a toml::Table,
+    path: Vec<String>,
+    server_table_list: &mut Vec<(Vec<String>, &'a toml::Table)>,
+) {
+    for (key, value) in table.iter() {
+        if path.is_empty() && key == "sssh" {
+            continue;
+        }
+        if let Some(entry_table) = value.as_table() {
+            let mut entry_path = path.clone();
+            entry_path.push(key.clone());
+            if entry_table.contains_key("hostname") || entry_table.contains_key("extends") {
+                server_table_list.push((entry_path, entry_table));
+            } else {
+                collect_server_tables(entry_table, entry_path, server_table_list);
+            }
+        }
+    }
+}
+
+fn check_servers(source_map: &SourceMap, config: &Config, diagnostic_list: &mut Vec<Diagnostic>) {
+    let mut server_list = config
+        .servers
+        .values()
+        // Inventory servers are not in any configuration file:
+        .filter(|server| !server.table_path.is_empty())
+        .collect::<Vec<_>>();
+    server_list.sort_by_key(|server| server.name.clone());
+    let field_diagnostic = |server: &ConfigServer, field: &str, message: String| {
+        let mut path = server.table_path.clone();
+        path.push(field.to_string());
+        source_map.diagnostic(&server.filename, &path, Severity::Error, message)
+    };
+    let mut address_map: HashMap<(String, u16), &ConfigServer> = HashMap::new();
+    for server in server_list {
+        if server.hostname.trim().is_empty() {
+            diagnostic_list.push(field_diagnostic(
+                server,
+                "hostname",
+                format!("server {:?} has an empty hostname", server.name),
+            ));
+        }
+        if server.port == 0 {
+            diagnostic_list.push(field_diagnostic(
+                server,
+                "port",
+                format!("server {:?} has port 0", server.name),
+            ));
+        }
+        if server
+            .username_list
+            .iter()
+            .any(|username| username.trim().is_empty())
+        {
+            diagnostic_list.push(field_diagnostic(
+                server,
+                "users",
+                format!("server {:?} has an empty username", server.name),
+            ));
+        }
+        if server.hostname.trim().is_empty() {
+            continue;
+        }
+        let address = (server.hostname.clone(), server.port);
+        match address_map.get(&address) {
+            Some(other_server) => {
+                let mut diagnostic = field_diagnostic(
+                    server,
+                    "hostname",
+                    format!(
+                        "server {:?} has the same hostname and port as {:?}",
+                        server.name, other_server.name
+                    ),
+                );
+                diagnostic.severity = Severity::Warning;
+                diagnostic_list.push(diagnostic);
+            }
+            None => {
+                address_map.insert(address, server);
+            }
+        }
+    }
+}
+
+/// Finds byte range of the value of `path` in `source`. If `path` doesn't exist, the closest
+/// table that contains it is used, and a table is found by its first value.
+pub fn locate(source: &str, path: &[String]) -> Option<Range<usize>> {
+    let table = toml::from_str::<toml::Table>(source).ok()?;
+    let get = |path: &[String]| {
+        let (first_key, key_list) = path.split_first()?;
+        key_list
+            .iter()
+            .try_fold(table.get(first_key)?, |value, key| match value {
+                toml::Value::Table(table) => table.get(key),
+                toml::Value::Array(array) => key.parse::<usize>().ok().and_then(|i| array.get(i)),
+                _ => None,
+            })
+    };
+    let mut path = path.to_vec();
+    loop {
+        match get(&path) {
+            // Values of standard tables have no span, only the values inside them:
+            Some(toml::Value::Table(table)) => path.push(table.keys().next()?.clone()),
+            Some(toml::Value::Array(array))
+                if array.first().is_some_and(|value| value.is_table()) =>
+            {
+                path.push("0".to_string())
+            }
+            Some(_) => {
+                return PathSeed { path: &path }
+                    .deserialize(toml::Deserializer::new(source))
+                    .ok()
+                    .flatten()
+            }
+            None => {
+                path.pop()?;
+            }
+        }
+    }
+}
+
+/// Line and column of byte `offset` of `source`.
+fn position(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before
+        .rsplit('\n')
+        .next()
+        .map(|line| line.chars().count())
+        .unwrap_or_default()
+        + 1;
+    (line, column)
+}
+
+/// Deserializes only the value at `path` with its span and ignores everything else.
+struct PathSeed<'a> {
+    path: &'a [String],
+}
+
+impl<'de> DeserializeSeed<'de> for PathSeed<'_> {
+    type Value = Option<Range<usize>>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        if self.path.is_empty() {
+            return toml::Spanned::<IgnoredAny>::deserialize(deserializer)
+                .map(|spanned| Some(spanned.span()));
+        }
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for PathSeed<'_> {
+    type Value = Option<Range<usize>>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "a table or an array")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut maybe_span = None;
+        while let Some(key) = map.next_key::<String>()? {
+            if key == self.path[0] {
+                maybe_span = map.next_value_seed(PathSeed {
+                    path: &self.path[1..],
+                })?;
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        Ok(maybe_span)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let index = self.path[0].parse::<usize>().unwrap_or(usize::MAX);
+        let mut maybe_span = None;
+        for current_index in 0.. {
+            if current_index == index {
+                match seq.next_element_seed(PathSeed {
+                    path: &self.path[1..],
+                })? {
+                    Some(span) => maybe_span = span,
+                    None => break,
+                }
+            } else if seq.next_element::<IgnoredAny>()?.is_none() {
+                break;
+            }
+        }
+        Ok(maybe_span)
+    }
+}